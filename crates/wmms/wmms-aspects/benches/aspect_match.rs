@@ -0,0 +1,53 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use wmms_aspects::bits::AspectBits;
+use wmms_aspects::query::AspectQuery;
+use wmms_aspects::registry::{AspectRegistry, AspectRegistryBuilder};
+use wmms_aspects::set::AspectSet;
+use wmms_core::rng::DetRng;
+
+/// 4 096 leaves under 16 × 16 namespaces, plus their ancestors.
+fn registry() -> AspectRegistry {
+    let mut b = AspectRegistryBuilder::new();
+    for ns in 0..16 {
+        for group in 0..16 {
+            for leaf in 0..16 {
+                b.register(&format!("ns{ns}.g{group}.a{leaf}")).unwrap();
+            }
+        }
+    }
+    b.seal().unwrap()
+}
+
+fn entities(reg: &AspectRegistry, count: usize, per_entity: usize) -> Vec<AspectSet> {
+    let mut rng = DetRng::from_seed_bytes([7; 32]);
+    (0..count)
+        .map(|_| {
+            let direct: Vec<_> = (0..per_entity)
+                .map(|_| wmms_aspects::registry::AspectRid(rng.below(reg.len() as u64) as u32))
+                .collect();
+            reg.close_under_ancestors(&direct)
+        })
+        .collect()
+}
+
+fn bench_matching(c: &mut Criterion) {
+    let reg = registry();
+    let sets = entities(&reg, 1_000, 24);
+    let bits: Vec<AspectBits> = sets.iter().map(|s| AspectBits::from_set(s, reg.len())).collect();
+    let query = AspectQuery::compile("all_of(ns3) any_of(ns3.g1.*, ns7.g2.*) none_of(ns9.g9, ns12)", &reg).unwrap();
+    let mask = query.to_mask();
+
+    let mut group = c.benchmark_group("aspect_match");
+    group.bench_function("vec_set", |b| {
+        b.iter(|| sets.iter().filter(|s| query.matches(black_box(s))).count())
+    });
+    group.bench_function("bitset_mask", |b| {
+        b.iter(|| bits.iter().filter(|s| mask.matches(black_box(s))).count())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_matching);
+criterion_main!(benches);
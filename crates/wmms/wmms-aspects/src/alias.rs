@@ -0,0 +1,43 @@
+use alloc::string::String;
+
+use crate::registry::AspectRid;
+
+/// Marks a node or alias as deprecated.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    pub note: Option<String>,
+}
+
+/// An old or alternative path that resolves to an existing node.
+///
+/// An alias also covers its subtree: with `class.mage` aliased to
+/// `class.wizard`, `class.mage.pyromancer` resolves to `class.wizard.pyromancer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AspectAlias {
+    pub target: AspectRid,
+    pub deprecation: Option<Deprecation>,
+}
+
+/// Result of resolving a path through the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    pub rid: AspectRid,
+    /// Set when the path, or the alias it went through, is deprecated.
+    pub deprecated: Option<DeprecationNotice>,
+}
+
+/// Warning emitted when a deprecated path is resolved.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "std", derive(miette::Diagnostic), diagnostic(severity(Warning)))]
+#[error(
+    "aspect '{path}' is deprecated{}{}",
+    replacement.as_ref().map(|r| alloc::format!(", use '{r}' instead")).unwrap_or_default(),
+    note.as_ref().map(|n| alloc::format!(": {n}")).unwrap_or_default()
+)]
+pub struct DeprecationNotice {
+    /// The path as written, in canonical form.
+    pub path: String,
+    /// Canonical path of the node it resolved to, if it differs from `path`.
+    pub replacement: Option<String>,
+    pub note: Option<String>,
+}
@@ -0,0 +1,212 @@
+use alloc::vec::Vec;
+
+use crate::query::AspectQuery;
+use crate::registry::{AspectRegistry, AspectRid};
+use crate::set::AspectSet;
+
+const WORD_BITS: u32 = u64::BITS;
+
+#[inline]
+fn split(rid: AspectRid) -> (u32, u64) {
+    (rid.0 / WORD_BITS, 1u64 << (rid.0 % WORD_BITS))
+}
+
+/// Bitset form of an [`AspectSet`], sized for one registry.
+///
+/// Small sets keep only their non-zero words (sparse), larger ones every word
+/// of the registry (dense); the representation is picked on construction and
+/// never affects equality or iteration, which is in ascending RID order like
+/// `AspectSet`.
+#[derive(Clone, Debug)]
+pub struct AspectBits {
+    repr: Repr,
+    num_words: u32,
+}
+
+#[derive(Clone, Debug)]
+enum Repr {
+    Dense(Vec<u64>),
+    /// Non-zero words sorted by index.
+    Sparse(Vec<(u32, u64)>),
+}
+
+impl AspectBits {
+    /// Empty set able to hold every RID of a registry with `len` aspects.
+    pub fn new(len: usize) -> Self {
+        Self {
+            repr: Repr::Sparse(Vec::new()),
+            num_words: len.div_ceil(WORD_BITS as usize) as u32,
+        }
+    }
+
+    pub fn for_registry(registry: &AspectRegistry) -> Self {
+        Self::new(registry.len())
+    }
+
+    /// Converts `set`, choosing the smaller representation.
+    ///
+    /// Panics if `set` holds a RID outside the registry size `len`.
+    pub fn from_set(set: &AspectSet, len: usize) -> Self {
+        let mut bits = Self::new(len);
+        let mut words: Vec<(u32, u64)> = Vec::new();
+        for &rid in set.as_slice() {
+            let (w, m) = split(rid);
+            assert!(w < bits.num_words, "aspect rid {} out of range for {len} aspects", rid.0);
+            match words.last_mut() {
+                Some((last, word)) if *last == w => *word |= m,
+                _ => words.push((w, m)),
+            }
+        }
+        // A sparse entry costs two dense words.
+        bits.repr = if words.len() * 2 > bits.num_words as usize {
+            let mut dense = alloc::vec![0u64; bits.num_words as usize];
+            for (w, m) in words {
+                dense[w as usize] = m;
+            }
+            Repr::Dense(dense)
+        } else {
+            Repr::Sparse(words)
+        };
+        bits
+    }
+
+    pub fn to_set(&self) -> AspectSet {
+        AspectSet::from_unsorted(self.iter().collect())
+    }
+
+    pub fn is_dense(&self) -> bool {
+        matches!(self.repr, Repr::Dense(_))
+    }
+
+    #[inline]
+    fn word(&self, index: u32) -> u64 {
+        match &self.repr {
+            Repr::Dense(words) => words.get(index as usize).copied().unwrap_or(0),
+            Repr::Sparse(words) => match words.binary_search_by_key(&index, |(w, _)| *w) {
+                Ok(pos) => words[pos].1,
+                Err(_) => 0,
+            },
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, rid: AspectRid) -> bool {
+        let (w, m) = split(rid);
+        self.word(w) & m != 0
+    }
+
+    /// Panics if `rid` is outside the registry size the set was built for.
+    pub fn insert(&mut self, rid: AspectRid) {
+        let (w, m) = split(rid);
+        assert!(w < self.num_words, "aspect rid {} out of range", rid.0);
+        match &mut self.repr {
+            Repr::Dense(words) => words[w as usize] |= m,
+            Repr::Sparse(words) => match words.binary_search_by_key(&w, |(i, _)| *i) {
+                Ok(pos) => words[pos].1 |= m,
+                Err(pos) => words.insert(pos, (w, m)),
+            },
+        }
+    }
+
+    pub fn remove(&mut self, rid: AspectRid) {
+        let (w, m) = split(rid);
+        match &mut self.repr {
+            Repr::Dense(words) => {
+                if let Some(word) = words.get_mut(w as usize) {
+                    *word &= !m;
+                }
+            }
+            Repr::Sparse(words) => {
+                if let Ok(pos) = words.binary_search_by_key(&w, |(i, _)| *i) {
+                    words[pos].1 &= !m;
+                    if words[pos].1 == 0 {
+                        words.remove(pos);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words().map(|(_, word)| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words().all(|(_, word)| word == 0)
+    }
+
+    /// Non-zero words in index order.
+    fn words(&self) -> impl Iterator<Item = (u32, u64)> + '_ {
+        let (dense, sparse) = match &self.repr {
+            Repr::Dense(words) => (Some(words), None),
+            Repr::Sparse(words) => (None, Some(words)),
+        };
+        let dense = dense
+            .into_iter()
+            .flat_map(|words| words.iter().enumerate().map(|(i, w)| (i as u32, *w)));
+        dense.chain(sparse.into_iter().flat_map(|words| words.iter().copied())).filter(|(_, w)| *w != 0)
+    }
+
+    /// RIDs in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = AspectRid> + '_ {
+        self.words().flat_map(|(index, mut word)| {
+            core::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                Some(AspectRid(index * WORD_BITS + bit))
+            })
+        })
+    }
+}
+
+impl PartialEq for AspectBits {
+    fn eq(&self, other: &Self) -> bool {
+        self.words().eq(other.words())
+    }
+}
+
+impl Eq for AspectBits {}
+
+/// An [`AspectQuery`] compiled to per-word masks, so matching an
+/// [`AspectBits`] is a handful of word ANDs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryMask {
+    all_of: Vec<(u32, u64)>,
+    any_of: Vec<(u32, u64)>,
+    none_of: Vec<(u32, u64)>,
+}
+
+fn mask_words(rids: &[AspectRid]) -> Vec<(u32, u64)> {
+    let mut words: Vec<(u32, u64)> = Vec::new();
+    for &rid in rids {
+        let (w, m) = split(rid);
+        match words.binary_search_by_key(&w, |(i, _)| *i) {
+            Ok(pos) => words[pos].1 |= m,
+            Err(pos) => words.insert(pos, (w, m)),
+        }
+    }
+    words
+}
+
+impl QueryMask {
+    /// Same semantics as [`AspectQuery::matches`].
+    #[inline]
+    pub fn matches(&self, bits: &AspectBits) -> bool {
+        self.all_of.iter().all(|&(w, m)| bits.word(w) & m == m)
+            && self.none_of.iter().all(|&(w, m)| bits.word(w) & m == 0)
+            && (self.any_of.is_empty() || self.any_of.iter().any(|&(w, m)| bits.word(w) & m != 0))
+    }
+}
+
+impl AspectQuery {
+    pub fn to_mask(&self) -> QueryMask {
+        QueryMask {
+            all_of: mask_words(&self.all_of),
+            any_of: mask_words(&self.any_of),
+            none_of: mask_words(&self.none_of),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use alloc::vec::Vec;
+
+use wmms_core::prelude::*;
+
+use crate::bits::AspectBits;
+use crate::registry::{AspectRegistry, AspectRid};
+use crate::set::AspectSet;
+
+/// An old path that only survives through an alias of the new registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovedAspect {
+    pub from: CanonicalKey,
+    pub to: CanonicalKey,
+}
+
+/// Differences between two sealed registries, by canonical path.
+///
+/// A path kept in both registries is unchanged even if its RID moved; only
+/// `remap` records that. Paths of `from` that are gone but still resolve
+/// through an alias of `to` are listed in `aliased`, and those whose parent
+/// no longer corresponds also in `reparented`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryDiff {
+    pub from_hash: Hash128,
+    pub to_hash: Hash128,
+    /// Paths of `to` that nothing in `from` maps to.
+    pub added: Vec<CanonicalKey>,
+    /// Paths of `from` that do not resolve in `to`.
+    pub removed: Vec<CanonicalKey>,
+    pub aliased: Vec<MovedAspect>,
+    pub reparented: Vec<MovedAspect>,
+    pub remap: RidRemap,
+}
+
+impl RegistryDiff {
+    /// Compares `from` (e.g. the registry a save was written with) to `to`.
+    pub fn between(from: &AspectRegistry, to: &AspectRegistry) -> Self {
+        let mut map: Vec<Option<AspectRid>> = Vec::with_capacity(from.len());
+        let mut aliased = Vec::new();
+        let mut reparented = Vec::new();
+        let mut removed = Vec::new();
+        // Parents come before their children, so they are already mapped.
+        for node in from.nodes() {
+            if let Some(rid) = to.rid_of_key(&node.key) {
+                map.push(Some(rid));
+            } else if let Some(res) = to.resolve(node.key.as_str()) {
+                let moved = MovedAspect { from: node.key.clone(), to: to.key(res.rid).clone() };
+                if to.parent(res.rid) != node.parent.and_then(|p| map[p.0 as usize]) {
+                    reparented.push(moved.clone());
+                }
+                aliased.push(moved);
+                map.push(Some(res.rid));
+            } else {
+                removed.push(node.key.clone());
+                map.push(None);
+            }
+        }
+
+        let mut reached = alloc::vec![false; to.len()];
+        for rid in map.iter().flatten() {
+            reached[rid.0 as usize] = true;
+        }
+        let added = to
+            .nodes()
+            .filter(|n| !reached[n.rid.0 as usize])
+            .map(|n| n.key.clone())
+            .collect();
+
+        Self {
+            from_hash: from.registry_hash,
+            to_hash: to.registry_hash,
+            added,
+            removed,
+            aliased,
+            reparented,
+            remap: RidRemap { map, to_len: to.len() },
+        }
+    }
+
+    /// True when both registries declare the same paths.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.aliased.is_empty()
+    }
+}
+
+/// Old-to-new `AspectRid` table produced by [`RegistryDiff`].
+///
+/// Several old RIDs may map to the same new one when aliases merge nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RidRemap {
+    map: Vec<Option<AspectRid>>,
+    to_len: usize,
+}
+
+impl RidRemap {
+    /// The new RID of `old`, or `None` if its path was removed.
+    pub fn get(&self, old: AspectRid) -> Option<AspectRid> {
+        self.map.get(old.0 as usize).copied().flatten()
+    }
+
+    /// True when every RID keeps its number, so stored data can be reused as is.
+    pub fn is_identity(&self) -> bool {
+        self.map.len() == self.to_len
+            && self.map.iter().enumerate().all(|(i, rid)| *rid == Some(AspectRid(i as u32)))
+    }
+
+    /// Maps a stored set into `to`, dropping removed aspects and closing the
+    /// result under its new ancestors.
+    pub fn remap_set(&self, set: &AspectSet, to: &AspectRegistry) -> AspectSet {
+        let mapped: Vec<AspectRid> = set.as_slice().iter().filter_map(|&rid| self.get(rid)).collect();
+        to.close_under_ancestors(&mapped)
+    }
+
+    pub fn remap_bits(&self, bits: &AspectBits, to: &AspectRegistry) -> AspectBits {
+        AspectBits::from_set(&self.remap_set(&bits.to_set(), to), to.len())
+    }
+}
@@ -1,9 +1,44 @@
+use alloc::boxed::Box;
 use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
 use wmms_core::error::WMMSCoreError;
 
-pub type AspectResult<t> = core::result::Result<t, AspectError>;
+use crate::diff::RegistryDiff;
+use crate::policy::PolicyViolation;
 
-#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+// The miette derive expands to `std::` paths, `vec!` and `format!`.
+#[cfg(feature = "std")]
+use std::{format, vec};
+
+pub type AspectResult<T> = core::result::Result<T, AspectError>;
+
+/// Byte range into the source text of a query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Span> for miette::SourceSpan {
+    fn from(span: Span) -> Self {
+        span.range().into()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(feature = "std", derive(miette::Diagnostic))]
 pub enum AspectError {
     #[error(transparent)]
     Core(#[from] WMMSCoreError),
@@ -11,12 +46,48 @@ pub enum AspectError {
     #[error("invalid aspect path: {0}")]
     InvalidPath(String),
 
-    #[error("unknown aspect: {0}")]
-    UnknownAspect(String),
+    /// `span` locates the path in the query source, if it came from one.
+    #[error("unknown aspect: {path}")]
+    UnknownAspect {
+        path: String,
+        #[cfg_attr(feature = "std", label("not in the registry"))]
+        span: Option<Span>,
+    },
+
+    #[error("invalid aspect query: {message}")]
+    InvalidQuery {
+        message: String,
+        #[cfg_attr(feature = "std", label("here"))]
+        span: Option<Span>,
+    },
+
+    #[error("aspect taxonomy breaks the naming policy ({} violations)", violations.len())]
+    Policy {
+        #[cfg_attr(feature = "std", related)]
+        violations: Vec<PolicyViolation>,
+    },
+
+    #[error("aspects '{first}' and '{second}' are mutually exclusive under '{group}'")]
+    Exclusive {
+        group: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("aspect '{0}' belongs to a base layer and cannot be changed by an extension")]
+    BaseLayer(String),
 
     #[error("registry is sealed")]
     Sealed,
 
     #[error("duplicate aspect path: {0}")]
     Duplicate(String),
+
+    /// Data was written against a different taxonomy; the diff carries the
+    /// RID remap needed to migrate it.
+    #[error(
+        "aspect registry changed: {} added, {} removed, {} aliased, {} reparented",
+        .0.added.len(), .0.removed.len(), .0.aliased.len(), .0.reparented.len()
+    )]
+    RegistryMismatch(Box<RegistryDiff>),
 }
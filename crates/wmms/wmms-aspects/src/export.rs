@@ -0,0 +1,235 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::registry::{AspectNode, AspectRegistry, AspectRid};
+
+/// Renders the aspect tree as Graphviz DOT, Mermaid or JSON.
+///
+/// Nodes are walked depth-first from the roots in canonical path order and
+/// identified by path, so the output only changes when the taxonomy does.
+pub struct TreeExport<'a> {
+    registry: &'a AspectRegistry,
+    usage: Option<&'a [u64]>,
+}
+
+impl<'a> TreeExport<'a> {
+    pub fn new(registry: &'a AspectRegistry) -> Self {
+        Self { registry, usage: None }
+    }
+
+    /// Adds usage counts, indexed by RID (e.g. per-aspect entity counts).
+    pub fn with_usage(mut self, counts: &'a [u64]) -> Self {
+        self.usage = Some(counts);
+        self
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph aspects {\n  rankdir=LR;\n  node [shape=box];\n");
+        self.walk(|node| {
+            let id = node.key.as_str();
+            let mut attrs = format!("label=\"{}\"", escape(&self.label(node)));
+            if let Some(color) = &node.meta.color {
+                let _ = write!(attrs, ", color=\"{}\"", escape(color));
+            }
+            if let Some(text) = &node.meta.description {
+                let _ = write!(attrs, ", tooltip=\"{}\"", escape(text));
+            }
+            if let Some(url) = node.meta.doc_links.first() {
+                let _ = write!(attrs, ", URL=\"{}\"", escape(url));
+            }
+            if node.exclusive {
+                attrs.push_str(", peripheries=2");
+            }
+            if node.deprecation.is_some() {
+                attrs.push_str(", style=dashed");
+            }
+            let _ = writeln!(out, "  \"{id}\" [{attrs}];");
+            if let Some(parent) = node.parent {
+                let _ = writeln!(out, "  \"{}\" -> \"{id}\";", self.registry.key(parent).as_str());
+            }
+        });
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("graph LR\n");
+        let mut styles = String::new();
+        self.walk(|node| {
+            let id = mermaid_id(node);
+            let _ = writeln!(out, "  {id}[\"{}\"]", self.label(node).replace('"', "#quot;"));
+            if let Some(parent) = node.parent {
+                let _ = writeln!(out, "  {} --> {id}", mermaid_id(self.registry.node(parent)));
+            }
+            // Style values can't be quoted, so colors other than plain
+            // names and hex codes are left out rather than break the graph.
+            if let Some(color) = node.meta.color.as_deref().filter(|c| is_plain_color(c)) {
+                let _ = writeln!(styles, "  style {id} fill:{color}");
+            }
+            if let Some(url) = node.meta.doc_links.first() {
+                let _ = writeln!(styles, "  click {id} \"{}\"", percent_escape(url));
+            }
+            if node.deprecation.is_some() {
+                let _ = writeln!(styles, "  class {id} deprecated");
+            }
+        });
+        if styles.contains("deprecated") {
+            out.push_str("  classDef deprecated stroke-dasharray: 5 5\n");
+        }
+        out.push_str(&styles);
+        out
+    }
+
+    /// Pretty-printed JSON with the registry and layer hashes and the node
+    /// tree; metadata fields are only written when set.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        let _ = writeln!(out, "  \"registry_hash\": \"{:032x}\",", self.registry.registry_hash.raw());
+        out.push_str("  \"layers\": [");
+        for (i, layer) in self.registry.layers().iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            let _ = write!(
+                out,
+                "{sep}\n    {{\"name\": {}, \"hash\": \"{:032x}\", \"len\": {}}}",
+                json_str(&layer.name),
+                layer.hash.raw(),
+                layer.len
+            );
+        }
+        out.push_str("\n  ],\n  \"roots\": [");
+        let roots: Vec<AspectRid> = self.registry.roots().collect();
+        self.json_nodes(&mut out, &roots, 2);
+        out.push_str("\n  ]\n}\n");
+        out
+    }
+
+    fn json_nodes(&self, out: &mut String, rids: &[AspectRid], level: usize) {
+        let pad = "  ".repeat(level);
+        for (i, &rid) in rids.iter().enumerate() {
+            let node = self.registry.node(rid);
+            let meta = &node.meta;
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            let _ = write!(out, "{pad}{{\"path\": {}", json_str(node.key.as_str()));
+            for (field, value) in [
+                ("display_name", &meta.display_name),
+                ("description", &meta.description),
+                ("color", &meta.color),
+                ("icon", &meta.icon),
+            ] {
+                if let Some(v) = value {
+                    let _ = write!(out, ", \"{field}\": {}", json_str(v));
+                }
+            }
+            if !meta.doc_links.is_empty() {
+                let links: Vec<String> = meta.doc_links.iter().map(|l| json_str(l)).collect();
+                let _ = write!(out, ", \"doc_links\": [{}]", links.join(", "));
+            }
+            if node.exclusive {
+                out.push_str(", \"exclusive\": true");
+            }
+            if let Some(d) = &node.deprecation {
+                let note = d.note.as_deref().map(json_str).unwrap_or_else(|| "true".into());
+                let _ = write!(out, ", \"deprecated\": {note}");
+            }
+            if let Some(count) = self.count(rid) {
+                let _ = write!(out, ", \"usage\": {count}");
+            }
+            let children = self.registry.children(rid);
+            if children.is_empty() {
+                out.push('}');
+            } else {
+                out.push_str(", \"children\": [");
+                self.json_nodes(out, children, level + 1);
+                let _ = write!(out, "\n{pad}]}}");
+            }
+        }
+    }
+
+    fn walk(&self, mut visit: impl FnMut(&AspectNode)) {
+        let mut below = Vec::new();
+        for root in self.registry.roots() {
+            visit(self.registry.node(root));
+            self.registry.descendants(root, None, &mut below);
+            for &rid in &below {
+                visit(self.registry.node(rid));
+            }
+        }
+    }
+
+    fn count(&self, rid: AspectRid) -> Option<u64> {
+        self.usage.and_then(|u| u.get(rid.0 as usize).copied())
+    }
+
+    /// Display name or last path segment, with the usage count if known.
+    fn label(&self, node: &AspectNode) -> String {
+        let path = node.key.as_str();
+        let name = node.meta.display_name.as_deref().unwrap_or_else(|| path.rsplit('.').next().unwrap_or(path));
+        match self.count(node.rid) {
+            Some(n) => format!("{name} ({n})"),
+            None => name.into(),
+        }
+    }
+}
+
+/// Prefixed so that paths like `class` or `end` don't clash with Mermaid keywords.
+///
+/// Dots become `__` and any other non-alphanumeric character `_<hex>_`, so
+/// distinct paths such as `a.b_c` and `a_b.c` never share an id.
+fn mermaid_id(node: &AspectNode) -> String {
+    let mut out = String::from("a_");
+    for c in node.key.as_str().chars() {
+        match c {
+            '.' => out.push_str("__"),
+            c if c.is_ascii_alphanumeric() => out.push(c),
+            c => {
+                let _ = write!(out, "_{:x}_", c as u32);
+            }
+        }
+    }
+    out
+}
+
+fn is_plain_color(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c == '#' || c.is_ascii_alphanumeric())
+}
+
+/// Percent-encodes quotes, whitespace and control characters in a link.
+fn percent_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '"' || c.is_whitespace() || c.is_control() {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(out, "%{b:02X}");
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
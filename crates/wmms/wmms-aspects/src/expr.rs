@@ -0,0 +1,154 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use wmms_core::hash::StableHash;
+
+use crate::error::AspectResult;
+use crate::query::{AspectQuery, DeprecatedUsage, parse::QueryParser};
+use crate::registry::{AspectRegistry, AspectRid};
+use crate::set::AspectSet;
+
+/// Boolean expression over aspects, for rules a flat [`AspectQuery`] cannot
+/// express, e.g. `(class.mage and not state.silenced) or trait.scroll_user`.
+///
+/// [`AspectExpr::normalize`] gives a canonical form: equivalent spellings of
+/// the same rule up to flattening, ordering, duplicates, double negation and
+/// constants normalize to the same tree, and so to the same stable hash.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, StableHash)]
+pub enum AspectExpr {
+    True,
+    False,
+    Has(AspectRid),
+    Not(Box<AspectExpr>),
+    And(Vec<AspectExpr>),
+    Or(Vec<AspectExpr>),
+}
+
+impl AspectExpr {
+    /// Compiles the text form against a sealed registry.
+    ///
+    /// Terms combine with `and`, `or`, `not` and parentheses, `and` binding
+    /// tighter than `or`. A term is an aspect pattern as accepted by
+    /// [`AspectQuery::compile`] (a wildcard is the `or` of its expansion) or an
+    /// `all_of(..)` / `any_of(..)` / `none_of(..)` clause. Empty input is `True`.
+    pub fn compile(src: &str, registry: &AspectRegistry) -> AspectResult<Self> {
+        QueryParser::new(src, registry).parse_expr()
+    }
+
+    /// Like [`AspectExpr::compile`], also returning the deprecated paths used.
+    pub fn compile_with_usages(src: &str, registry: &AspectRegistry) -> AspectResult<(Self, Vec<DeprecatedUsage>)> {
+        let mut parser = QueryParser::new(src, registry);
+        let expr = parser.parse_expr()?;
+        Ok((expr, parser.deprecated))
+    }
+
+    pub fn all(terms: impl IntoIterator<Item = AspectExpr>) -> Self {
+        AspectExpr::And(terms.into_iter().collect())
+    }
+
+    pub fn any(terms: impl IntoIterator<Item = AspectExpr>) -> Self {
+        AspectExpr::Or(terms.into_iter().collect())
+    }
+
+    /// Canonical form of this expression.
+    ///
+    /// Nested `And`/`Or` are flattened, operands sorted and deduplicated,
+    /// `not not x` becomes `x`, and constants are folded away. An `And`
+    /// containing both `x` and `not x` becomes `False` (dually for `Or`).
+    pub fn normalize(self) -> Self {
+        match self {
+            AspectExpr::Not(inner) => match inner.normalize() {
+                AspectExpr::True => AspectExpr::False,
+                AspectExpr::False => AspectExpr::True,
+                AspectExpr::Not(x) => *x,
+                x => AspectExpr::Not(Box::new(x)),
+            },
+            AspectExpr::And(terms) => Self::normalize_nary(terms, true),
+            AspectExpr::Or(terms) => Self::normalize_nary(terms, false),
+            leaf => leaf,
+        }
+    }
+
+    fn normalize_nary(terms: Vec<AspectExpr>, is_and: bool) -> Self {
+        // `identity` is dropped from the operands, `absorbing` decides the result.
+        let (identity, absorbing) = if is_and {
+            (AspectExpr::True, AspectExpr::False)
+        } else {
+            (AspectExpr::False, AspectExpr::True)
+        };
+
+        let mut flat = Vec::with_capacity(terms.len());
+        let mut pending = terms;
+        while let Some(term) = pending.pop() {
+            match term.normalize() {
+                AspectExpr::And(inner) if is_and => pending.extend(inner),
+                AspectExpr::Or(inner) if !is_and => pending.extend(inner),
+                t if t == identity => {}
+                t if t == absorbing => return absorbing,
+                t => flat.push(t),
+            }
+        }
+        flat.sort();
+        flat.dedup();
+
+        let complementary = flat.iter().any(|t| match t {
+            AspectExpr::Not(inner) => flat.binary_search(inner).is_ok(),
+            _ => false,
+        });
+        if complementary {
+            return absorbing;
+        }
+
+        match flat.len() {
+            0 => identity,
+            1 => flat.pop().expect("one operand"),
+            _ if is_and => AspectExpr::And(flat),
+            _ => AspectExpr::Or(flat),
+        }
+    }
+
+    /// Evaluates against an entity's aspect set, which is expected to be
+    /// closed under ancestors like the sets the model stores.
+    pub fn matches(&self, aspects: &AspectSet) -> bool {
+        match self {
+            AspectExpr::True => true,
+            AspectExpr::False => false,
+            AspectExpr::Has(rid) => aspects.contains(*rid),
+            AspectExpr::Not(inner) => !inner.matches(aspects),
+            AspectExpr::And(terms) => terms.iter().all(|t| t.matches(aspects)),
+            AspectExpr::Or(terms) => terms.iter().any(|t| t.matches(aspects)),
+        }
+    }
+
+    /// Calls `f` for every aspect mentioned, in tree order.
+    pub fn for_each_aspect(&self, f: &mut impl FnMut(AspectRid)) {
+        match self {
+            AspectExpr::True | AspectExpr::False => {}
+            AspectExpr::Has(rid) => f(*rid),
+            AspectExpr::Not(inner) => inner.for_each_aspect(f),
+            AspectExpr::And(terms) | AspectExpr::Or(terms) => {
+                terms.iter().for_each(|t| t.for_each_aspect(f));
+            }
+        }
+    }
+}
+
+impl core::ops::Not for AspectExpr {
+    type Output = AspectExpr;
+
+    fn not(self) -> Self::Output {
+        AspectExpr::Not(Box::new(self))
+    }
+}
+
+/// `all_of` ∧ (`any_of`, if non-empty) ∧ ¬`none_of`, normalized.
+impl From<&AspectQuery> for AspectExpr {
+    fn from(q: &AspectQuery) -> Self {
+        let has = |rids: &[AspectRid]| rids.iter().copied().map(AspectExpr::Has).collect::<Vec<_>>();
+        let mut terms = has(&q.all_of);
+        if !q.any_of.is_empty() {
+            terms.push(AspectExpr::Or(has(&q.any_of)));
+        }
+        terms.push(!AspectExpr::Or(has(&q.none_of)));
+        AspectExpr::And(terms).normalize()
+    }
+}
@@ -0,0 +1,26 @@
+use alloc::string::String;
+
+use wmms_core::hash::Hash128;
+
+use crate::registry::AspectRid;
+
+/// One layer of a registry: the base taxonomy or an extension such as a mod
+/// or content pack.
+///
+/// A layer owns a contiguous RID range after the layers below it, so adding
+/// a layer never renumbers them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AspectLayer {
+    pub name: String,
+    pub start: AspectRid,
+    pub len: u32,
+    /// Hash of the nodes this layer added, as `registry_hash` would be for a
+    /// registry holding only them.
+    pub hash: Hash128,
+}
+
+impl AspectLayer {
+    pub fn contains(&self, rid: AspectRid) -> bool {
+        (self.start.0..self.start.0 + self.len).contains(&rid.0)
+    }
+}
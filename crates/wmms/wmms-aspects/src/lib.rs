@@ -1,8 +1,18 @@
 #![no_std]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+pub mod alias;
+pub mod bits;
+pub mod diff;
 pub mod error;
+pub mod export;
+pub mod expr;
+pub mod layer;
+pub mod meta;
 pub mod path;
+pub mod policy;
 pub mod registry;
 pub mod set;
 pub mod query;
@@ -51,5 +61,330 @@ mod tests{
 
         assert!(q.matches(&aspects));
     }
+
+    #[test]
+    fn query_compiles_from_text() {
+        use crate::error::{AspectError, Span};
+        use crate::query::AspectQuery;
+
+        let mut b = AspectRegistryBuilder::new();
+        for p in ["class.mage.pyromancer.adept", "class.mage.cryomancer", "class.warrior", "state.silenced"] {
+            b.register(p).unwrap();
+        }
+        let r = b.seal().unwrap();
+        let rid = |p: &str| r.resolve_path(p).unwrap();
+
+        let q = AspectQuery::compile("class.mage any_of(class.mage.*{1}) none_of( state.silenced, )", &r).unwrap();
+        assert_eq!(q.all_of, vec![rid("class.mage")]);
+        assert_eq!(q.any_of, vec![rid("class.mage.cryomancer"), rid("class.mage.pyromancer")]);
+        assert_eq!(q.none_of, vec![rid("state.silenced")]);
+
+        let deep = AspectQuery::compile("any_of(class.*)", &r).unwrap();
+        assert_eq!(deep.any_of.len(), 5);
+
+        match AspectQuery::compile("all_of(class.mage, class.rogue)", &r) {
+            Err(AspectError::UnknownAspect { path, span }) => {
+                assert_eq!((path.as_str(), span), ("class.rogue", Some(Span::new(19, 30))));
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(matches!(
+            AspectQuery::compile("any_of(class.warrior.*)", &r),
+            Err(AspectError::InvalidQuery { .. })
+        ));
+        assert!(AspectQuery::compile("some_of(class)", &r).is_err());
+        assert!(AspectQuery::compile("all_of(class", &r).is_err());
+    }
+
+    #[test]
+    fn expressions_normalize_and_match() {
+        use alloc::vec::Vec;
+        use wmms_core::hash::StableHash;
+        use crate::expr::AspectExpr;
+
+        let mut b = AspectRegistryBuilder::new();
+        for p in ["class.mage", "class.warrior", "state.silenced", "trait.scroll_user"] {
+            b.register(p).unwrap();
+        }
+        let r = b.seal().unwrap();
+        let rid = |p: &str| r.resolve_path(p).unwrap();
+
+        let e = AspectExpr::compile("(class.mage and not state.silenced) or trait.scroll_user", &r).unwrap();
+        let same = AspectExpr::compile("trait.scroll_user or (not not not state.silenced and class.mage and class.mage)", &r).unwrap();
+        assert_eq!(e, same);
+        assert_eq!(e.stable_digest(), same.stable_digest());
+
+        let set = |ps: &[&str]| r.close_under_ancestors(&ps.iter().map(|p| rid(p)).collect::<Vec<_>>());
+        assert!(e.matches(&set(&["class.mage"])));
+        assert!(!e.matches(&set(&["class.mage", "state.silenced"])));
+        assert!(e.matches(&set(&["state.silenced", "trait.scroll_user"])));
+
+        assert_eq!(AspectExpr::compile("class.mage and not class.mage", &r).unwrap(), AspectExpr::False);
+        assert_eq!(AspectExpr::compile("not class or class", &r).unwrap(), AspectExpr::True);
+        assert!(AspectExpr::compile("class.mage and", &r).is_err());
+
+        let q = crate::query::AspectQuery::compile("all_of(class) none_of(state.silenced)", &r).unwrap();
+        let from_q = AspectExpr::from(&q);
+        assert_eq!(from_q, AspectExpr::compile("class and not state.silenced", &r).unwrap());
+    }
+
+    #[test]
+    fn query_explains_mismatches() {
+        use alloc::string::ToString;
+        use crate::query::AspectQuery;
+
+        let mut b = AspectRegistryBuilder::new();
+        for p in ["class.mage", "state.silenced", "state.stunned", "trait.scroll_user", "trait.arcane"] {
+            b.register(p).unwrap();
+        }
+        let r = b.seal().unwrap();
+        let rid = |p: &str| r.resolve_path(p).unwrap();
+        let q = AspectQuery::compile("all_of(class.mage) any_of(trait.*) none_of(state)", &r).unwrap();
+
+        let silenced = r.close_under_ancestors(&[rid("state.silenced")]);
+        let e = q.explain(&silenced, &r);
+        assert!(!e.is_match());
+        assert_eq!(e.missing, vec![rid("class.mage")]);
+        assert_eq!(e.blocked_by[0].rule, rid("state"));
+        assert_eq!(e.blocked_by[0].via, vec![rid("state.silenced")]);
+        assert_eq!(
+            e.display(&r).to_string(),
+            "missing all_of: class.mage\nblocked by none_of: state (via state.silenced)\nnone of any_of: trait.arcane, trait.scroll_user"
+        );
+
+        let ok = r.close_under_ancestors(&[rid("class.mage"), rid("trait.arcane")]);
+        assert!(q.explain(&ok, &r).is_match());
+        assert_eq!(q.explain(&ok, &r).display(&r).to_string(), "matched");
+    }
+
+    #[test]
+    fn bitset_matches_like_vec_set() {
+        use alloc::format;
+        use alloc::vec::Vec;
+        use crate::bits::AspectBits;
+        use crate::query::AspectQuery;
+        use crate::registry::AspectRid;
+
+        let mut b = AspectRegistryBuilder::new();
+        for i in 0..200 {
+            b.register(&format!("ns{}.a{i}", i % 3)).unwrap();
+        }
+        let r = b.seal().unwrap();
+        let q = AspectQuery::compile("all_of(ns1) any_of(ns1.a7, ns1.a130) none_of(ns2)", &r).unwrap();
+        let mask = q.to_mask();
+
+        for seed in 0..64u32 {
+            let direct: Vec<AspectRid> = (0..1 + seed % 40).map(|k| AspectRid((seed * 31 + k * 17) % r.len() as u32)).collect();
+            let set = r.close_under_ancestors(&direct);
+            let bits = AspectBits::from_set(&set, r.len());
+            assert_eq!(bits.iter().collect::<Vec<_>>(), set.as_slice());
+            assert_eq!(bits.to_set(), set);
+            assert_eq!(mask.matches(&bits), q.matches(&set), "{set:?}");
+        }
+
+        let mut sparse = AspectBits::for_registry(&r);
+        sparse.insert(AspectRid(130));
+        sparse.insert(AspectRid(3));
+        assert!(!sparse.is_dense());
+        let dense = AspectBits::from_set(&r.close_under_ancestors(&(0..150).map(AspectRid).collect::<Vec<_>>()), r.len());
+        assert!(dense.is_dense());
+        sparse.remove(AspectRid(130));
+        assert_eq!(sparse.iter().collect::<Vec<_>>(), [AspectRid(3)]);
+        assert_eq!(sparse, AspectBits::from_set(&AspectSet::from_unsorted(vec![AspectRid(3)]), r.len()));
+    }
+
+    #[test]
+    fn aliases_and_deprecations_resolve() {
+        use alloc::string::ToString;
+        use wmms_core::ids::AspectId;
+        use crate::error::AspectError;
+        use crate::query::{AspectQuery, rewrite_deprecated};
+
+        let mut b = AspectRegistryBuilder::new();
+        b.register("class.wizard.pyromancer").unwrap();
+        b.register("state.silenced").unwrap();
+        b.rename("class.mage", "class.wizard").unwrap();
+        b.alias("class.magus", "class.mage").unwrap();
+        b.deprecate("state.silenced", Some("use status.silence")).unwrap();
+        let r = b.seal().unwrap();
+
+        let mut plain = AspectRegistryBuilder::new();
+        plain.register("class.wizard.pyromancer").unwrap();
+        plain.register("state.silenced").unwrap();
+        assert_eq!(r.registry_hash, plain.seal().unwrap().registry_hash);
+
+        let wizard = r.resolve_path("class.wizard").unwrap();
+        let pyro = r.resolve_path("class.wizard.pyromancer").unwrap();
+        assert_eq!(r.resolve_path("class.mage"), Some(wizard));
+        assert_eq!(r.resolve_path("class.magus"), Some(wizard));
+        assert_eq!(r.resolve_path("class.mage.pyromancer"), Some(pyro));
+        assert_eq!(r.resolve_id(&AspectId::new("class.mage")), Some(wizard));
+        assert_eq!(r.resolve_path("class.mage.cryomancer"), None);
+
+        let old = r.resolve("class.mage.pyromancer").unwrap().deprecated.unwrap();
+        assert_eq!(old.to_string(), "aspect 'class.mage.pyromancer' is deprecated, use 'class.wizard.pyromancer' instead");
+        assert!(r.resolve("class.wizard").unwrap().deprecated.is_none());
+        let silenced = r.resolve("state.silenced").unwrap().deprecated.unwrap();
+        assert_eq!(silenced.to_string(), "aspect 'state.silenced' is deprecated: use status.silence");
+        assert_eq!(r.deprecated().map(|(k, _)| k.as_str()).collect::<alloc::vec::Vec<_>>(), ["class.mage", "state.silenced"]);
+
+        let src = "all_of(class.mage.*) none_of(state.silenced)";
+        let (q, usages) = AspectQuery::compile_with_usages(src, &r).unwrap();
+        assert_eq!(q.all_of, vec![pyro]);
+        assert_eq!(usages.len(), 2);
+        assert_eq!(rewrite_deprecated(src, &usages), "all_of(class.wizard.*) none_of(state.silenced)");
+
+        let mut bad = AspectRegistryBuilder::new();
+        bad.register("class.mage.x").unwrap();
+        bad.alias("class.mage", "class.wizard").unwrap();
+        assert!(matches!(bad.seal(), Err(AspectError::Duplicate(_))));
+
+        let mut cycle = AspectRegistryBuilder::new();
+        cycle.alias("a", "b").unwrap();
+        cycle.alias("b", "a").unwrap();
+        assert!(cycle.seal().is_err());
+    }
+
+    #[test]
+    fn registry_diff_remaps_rids() {
+        use alloc::string::ToString;
+        use crate::error::AspectError;
+
+        let mut b = AspectRegistryBuilder::new();
+        for p in ["class.mage", "class.pyromancer", "state.silenced", "state.rooted"] {
+            b.register(p).unwrap();
+        }
+        let old = b.seal().unwrap();
+
+        let mut b = AspectRegistryBuilder::new();
+        b.rename("class.mage", "class.wizard").unwrap();
+        b.rename("class.pyromancer", "class.wizard.pyromancer").unwrap();
+        b.register("state.silenced").unwrap();
+        b.register("status.stunned").unwrap();
+        let new = b.seal().unwrap();
+
+        let keys = |v: &[wmms_core::canon::CanonicalKey]| v.iter().map(|k| k.as_str().to_string()).collect::<alloc::vec::Vec<_>>();
+        let d = old.diff(&new);
+        assert_eq!(keys(&d.added), ["status", "status.stunned"]);
+        assert_eq!(keys(&d.removed), ["state.rooted"]);
+        assert_eq!(d.aliased.len(), 2);
+        assert_eq!(d.reparented.len(), 1);
+        assert_eq!(d.reparented[0].to.as_str(), "class.wizard.pyromancer");
+        assert!(!d.remap.is_identity());
+        assert!(old.diff(&old).remap.is_identity() && old.diff(&old).is_empty());
+
+        let rid = |r: &registry::AspectRegistry, p: &str| r.resolve_path(p).unwrap();
+        let stored = old.close_under_ancestors(&[rid(&old, "class.pyromancer"), rid(&old, "state.rooted")]);
+        let migrated = d.remap.remap_set(&stored, &new);
+        let expected = new.close_under_ancestors(&[rid(&new, "class.wizard.pyromancer"), rid(&new, "state")]);
+        assert_eq!(migrated, expected);
+
+        assert!(new.verify(&new).is_ok());
+        let err = new.verify(&old).unwrap_err();
+        assert_eq!(err.to_string(), "aspect registry changed: 2 added, 1 removed, 2 aliased, 1 reparented");
+        assert!(matches!(err, AspectError::RegistryMismatch(diff) if *diff == d));
+    }
+
+    #[test]
+    fn taxonomy_policy_reports_violations() {
+        use crate::error::AspectError;
+        use crate::policy::{TaxonomyPolicy, ViolationKind};
+
+        let policy = TaxonomyPolicy::default();
+        assert!(policy.check_path("status.burning").is_empty());
+        let v = policy.check_path("statis.Burning");
+        assert_eq!(v[0].kind, ViolationKind::NotLowercase);
+        assert_eq!(v[0].help.as_deref(), Some("write it as 'statis.burning'"));
+        assert_eq!(v[1].kind, ViolationKind::UnknownRoot("statis".into()));
+        assert_eq!(v[1].help.as_deref(), Some("did you mean 'status'?"));
+        let v = policy.check_path("state.not_stunned");
+        assert_eq!(v[0].help.as_deref(), Some("register 'state.stunned' and match it with none_of(state.stunned)"));
+
+        let mut b = AspectRegistryBuilder::new().with_policy(policy.clone());
+        b.register("status.on_fire.big").unwrap();
+        b.register("status.onfire").unwrap();
+        b.register("trait.x").unwrap();
+        let Err(AspectError::Policy { violations }) = b.seal() else { panic!("policy not enforced") };
+        let kinds: alloc::vec::Vec<_> = violations.iter().map(|v| (v.path.as_str(), &v.kind)).collect();
+        assert_eq!(kinds, [
+            ("status.onfire", &ViolationKind::Collides("status.on_fire".into())),
+            ("trait.x", &ViolationKind::UnknownRoot("trait".into())),
+        ]);
+        assert!(TaxonomyPolicy::permissive().check_paths(["trait.x"]).is_empty());
+    }
+
+    #[test]
+    fn extension_layers_keep_base_rids() {
+        use crate::error::AspectError;
+
+        let mut b = AspectRegistryBuilder::new();
+        b.register("class.mage").unwrap();
+        b.register("damage.fire").unwrap();
+        b.alias("class.wizard", "class.mage").unwrap();
+        let base = b.seal().unwrap();
+
+        let mut ext = AspectRegistryBuilder::extending(&base, "necromancy");
+        ext.register("class.mage.necromancer").unwrap();
+        ext.register("class.lich").unwrap();
+        ext.register("damage.fire").unwrap();
+        ext.alias("class.sorcerer", "class.wizard").unwrap();
+        let r = ext.seal().unwrap();
+
+        for node in base.nodes() {
+            assert_eq!(r.resolve_path(node.key.as_str()), Some(node.rid));
+        }
+        let necro = r.resolve_path("class.mage.necromancer").unwrap();
+        let lich = r.resolve_path("class.lich").unwrap();
+        assert_eq!((lich.0, necro.0), (base.len() as u32, base.len() as u32 + 1));
+        assert_eq!(r.parent(necro), base.resolve_path("class.mage"));
+        assert_eq!(r.resolve_path("class.sorcerer"), base.resolve_path("class.mage"));
+        let class = r.resolve_path("class").unwrap();
+        let children: alloc::vec::Vec<_> = r.children(class).iter().map(|&c| r.key(c).as_str()).collect();
+        assert_eq!(children, ["class.lich", "class.mage"]);
+
+        assert_eq!(r.layers().len(), 2);
+        assert_eq!(r.layers()[0].hash, base.registry_hash);
+        assert_eq!(r.layer_of(lich).name, "necromancy");
+        assert_ne!(r.registry_hash, base.registry_hash);
+
+        let mut bad = AspectRegistryBuilder::extending(&base, "bad");
+        bad.deprecate("damage.fire", None).unwrap();
+        assert!(matches!(bad.seal(), Err(AspectError::BaseLayer(p)) if p == "damage.fire"));
+    }
+
+    #[test]
+    fn tree_exports_are_deterministic() {
+        use crate::meta::AspectMeta;
+
+        let mut b = AspectRegistryBuilder::new();
+        b.register("state.stance.defensive").unwrap();
+        b.register("class.mage").unwrap();
+        b.exclusive("state.stance").unwrap();
+        b.describe("class.mage", AspectMeta::new().display_name("Mage \"arcane\"").color("#3366ff")).unwrap();
+        b.register("a.b_c").unwrap();
+        b.register("a_b.c").unwrap();
+        b.describe("a_b.c", AspectMeta::new().color("red; stroke:#000").doc_link("https://x.test/\"q\" a")).unwrap();
+        let r = b.seal().unwrap();
+        let usage: alloc::vec::Vec<u64> = (0..r.len() as u64).collect();
+        let mage = r.resolve_path("class.mage").unwrap();
+        let stance = r.resolve_path("state.stance").unwrap();
+
+        let dot = r.export().with_usage(&usage).to_dot();
+        assert!(dot.contains(&alloc::format!("\"class.mage\" [label=\"Mage \\\"arcane\\\" ({})\", color=\"#3366ff\"];", mage.0)));
+        assert!(dot.contains(&alloc::format!("\"state.stance\" [label=\"stance ({})\", peripheries=2];", stance.0)));
+        assert!(dot.find("\"class\" ->").unwrap() < dot.find("\"state\" ->").unwrap());
+
+        let mermaid = r.export().to_mermaid();
+        assert!(mermaid.contains("  a_class --> a_class__mage\n"));
+        assert!(mermaid.contains("  style a_class__mage fill:#3366ff\n"));
+        assert!(mermaid.contains("  a_a__b_5f_c[") && mermaid.contains("  a_a_5f_b__c["));
+        assert!(!mermaid.contains("stroke:#000"));
+        assert!(mermaid.contains("  click a_a_5f_b__c \"https://x.test/%22q%22%20a\"\n"));
+
+        let json = r.export().to_json();
+        assert!(json.contains("{\"path\": \"state.stance\", \"exclusive\": true, \"children\": ["));
+        assert!(json.contains("\"display_name\": \"Mage \\\"arcane\\\"\""));
+        assert_eq!(json, r.export().to_json());
+    }
 }
 
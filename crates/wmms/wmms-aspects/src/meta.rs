@@ -0,0 +1,50 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Authoring metadata attached to an aspect, for editors, docs and exports.
+///
+/// None of it affects matching or the registry hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AspectMeta {
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// Editor color, e.g. `#e25822`.
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub doc_links: Vec<String>,
+}
+
+impl AspectMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn display_name(mut self, name: impl Into<String>) -> Self {
+        self.display_name = Some(name.into());
+        self
+    }
+
+    pub fn description(mut self, text: impl Into<String>) -> Self {
+        self.description = Some(text.into());
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub fn icon(mut self, icon: impl Into<String>) -> Self {
+        self.icon = Some(icon.into());
+        self
+    }
+
+    pub fn doc_link(mut self, url: impl Into<String>) -> Self {
+        self.doc_links.push(url.into());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
@@ -0,0 +1,204 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::registry::AspectRegistry;
+
+/// Root namespaces recommended by the taxonomy guidance.
+pub const DEFAULT_ROOTS: &[&str] = &[
+    "ability", "class", "damage", "effect", "entity", "race", "signal", "state", "status",
+];
+
+/// Segment prefixes that mark a negative aspect such as `state.not_stunned`.
+pub const DEFAULT_NEGATIVE_PREFIXES: &[&str] = &["not_", "non_", "no_"];
+
+/// Naming rules for aspect paths.
+///
+/// Segments are always checked to be lowercase `[a-z0-9_]` starting with a
+/// letter; the rest is configurable, usually from the universe manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaxonomyPolicy {
+    /// Allowed first segments, or `None` to accept any namespace.
+    pub roots: Option<BTreeSet<String>>,
+    pub negative_prefixes: Vec<String>,
+}
+
+impl Default for TaxonomyPolicy {
+    fn default() -> Self {
+        Self {
+            roots: Some(DEFAULT_ROOTS.iter().map(|r| r.to_string()).collect()),
+            negative_prefixes: DEFAULT_NEGATIVE_PREFIXES.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+/// What rule a path breaks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    NotLowercase,
+    InvalidSegment(String),
+    UnknownRoot(String),
+    Negative(String),
+    /// Another path that only differs by case or separators.
+    Collides(String),
+}
+
+impl core::fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ViolationKind::NotLowercase => write!(f, "is not lowercase"),
+            ViolationKind::InvalidSegment(s) => write!(f, "has invalid segment '{s}'"),
+            ViolationKind::UnknownRoot(r) => write!(f, "uses unknown namespace '{r}'"),
+            ViolationKind::Negative(s) => write!(f, "is a negative aspect ('{s}')"),
+            ViolationKind::Collides(other) => write!(f, "collides with '{other}'"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "std", derive(miette::Diagnostic))]
+#[error("aspect '{path}' {kind}")]
+pub struct PolicyViolation {
+    pub path: String,
+    pub kind: ViolationKind,
+    #[cfg_attr(feature = "std", help)]
+    pub help: Option<String>,
+}
+
+impl TaxonomyPolicy {
+    /// Accepts any namespace and negative names; only segment spelling is checked.
+    pub fn permissive() -> Self {
+        Self { roots: None, negative_prefixes: Vec::new() }
+    }
+
+    /// Checks one path on its own, without collision detection.
+    pub fn check_path(&self, path: &str) -> Vec<PolicyViolation> {
+        let mut out = Vec::new();
+        let violation = |kind, help| PolicyViolation { path: path.to_string(), kind, help };
+
+        if path.chars().any(|c| c.is_ascii_uppercase()) {
+            let fixed = path.split('.').map(snake_case).collect::<Vec<_>>().join(".");
+            out.push(violation(ViolationKind::NotLowercase, Some(format!("write it as '{fixed}'"))));
+        } else if let Some(seg) = path.split('.').find(|s| !is_valid_segment(s)) {
+            let help = "segments start with a letter and use only a-z, 0-9 and '_'";
+            out.push(violation(ViolationKind::InvalidSegment(seg.to_string()), Some(help.to_string())));
+        }
+
+        let root = path.split('.').next().unwrap_or_default().to_ascii_lowercase();
+        if let Some(roots) = &self.roots
+            && !roots.contains(&root)
+        {
+            let help = match closest(&root, roots) {
+                Some(r) => format!("did you mean '{r}'?"),
+                None => format!("allowed namespaces: {}", roots.iter().cloned().collect::<Vec<_>>().join(", ")),
+            };
+            out.push(violation(ViolationKind::UnknownRoot(root), Some(help)));
+        }
+
+        let segments: Vec<String> = path.split('.').map(str::to_ascii_lowercase).collect();
+        for (i, seg) in segments.iter().enumerate() {
+            if let Some(positive) = self.negative_prefixes.iter().find_map(|p| seg.strip_prefix(p.as_str()))
+                && !positive.is_empty()
+            {
+                let mut fixed = segments.clone();
+                fixed[i] = positive.to_string();
+                let positive_path = fixed.join(".");
+                let help = format!("register '{positive_path}' and match it with none_of({positive_path})");
+                out.push(violation(ViolationKind::Negative(seg.to_string()), Some(help)));
+                break;
+            }
+        }
+        out
+    }
+
+    /// Checks a whole taxonomy.
+    ///
+    /// Paths with descendants in `paths` are only checked for collisions, since
+    /// their descendants already report what is wrong with them. Well-formed
+    /// paths that differ only by separators collide, and the later one is
+    /// reported.
+    pub fn check_paths<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> Vec<PolicyViolation> {
+        let paths: Vec<&str> = paths.into_iter().collect();
+        let all: BTreeSet<&str> = paths.iter().copied().collect();
+        let mut out = Vec::new();
+        let mut seen: BTreeMap<String, &str> = BTreeMap::new();
+        for path in paths {
+            let prefix = format!("{path}.");
+            let has_descendants = all.range(prefix.as_str()..).next().is_some_and(|q| q.starts_with(&prefix));
+            let own = self.check_path(path);
+            if !own.is_empty() {
+                if !has_descendants {
+                    out.extend(own);
+                }
+                continue;
+            }
+            match seen.get(&fold(path)) {
+                Some(&first) if first != path => out.push(PolicyViolation {
+                    path: path.to_string(),
+                    kind: ViolationKind::Collides(first.to_string()),
+                    help: Some(format!("use '{first}' or pick a distinct name")),
+                }),
+                Some(_) => {}
+                None => {
+                    seen.insert(fold(path), path);
+                }
+            }
+        }
+        out
+    }
+
+    /// Lints every node and alias of a sealed registry.
+    pub fn lint(&self, registry: &AspectRegistry) -> Vec<PolicyViolation> {
+        let nodes = registry.nodes().map(|n| n.key.as_str());
+        self.check_paths(nodes.chain(registry.aliases().map(|(k, _)| k.as_str())))
+    }
+}
+
+fn is_valid_segment(seg: &str) -> bool {
+    seg.starts_with(|c: char| c.is_ascii_lowercase())
+        && seg.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Case- and separator-insensitive form used to detect colliding names.
+fn fold(path: &str) -> String {
+    path.chars().filter(|c| !matches!(c, '_' | '-')).map(|c| c.to_ascii_lowercase()).collect()
+}
+
+fn snake_case(seg: &str) -> String {
+    let mut out = String::with_capacity(seg.len() + 2);
+    for (i, c) in seg.char_indices() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Nearest allowed root within two edits, for typo suggestions.
+fn closest<'a>(root: &str, roots: &'a BTreeSet<String>) -> Option<&'a str> {
+    roots
+        .iter()
+        .map(|r| (edit_distance(root, r), r))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, r)| r.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = alloc::vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            cur[j + 1] = (prev[j] + usize::from(ca != *cb)).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
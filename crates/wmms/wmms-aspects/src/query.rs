@@ -1,7 +1,38 @@
+use alloc::string::String;
 use alloc::vec::Vec;
-use crate::registry::AspectRid;
+use crate::alias::DeprecationNotice;
+use crate::error::{AspectResult, Span};
+use crate::registry::{AspectRegistry, AspectRid};
 use crate::set::AspectSet;
 
+mod explain;
+pub(crate) mod parse;
+
+pub use explain::{Blocker, ExplainDisplay, QueryExplain};
+
+/// A deprecated aspect path found in query or expression source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeprecatedUsage {
+    pub span: Span,
+    pub notice: DeprecationNotice,
+}
+
+/// Rewrites the usages that have a replacement (renamed or aliased paths) to
+/// the current canonical path; deprecated nodes without one are left as is.
+pub fn rewrite_deprecated(src: &str, usages: &[DeprecatedUsage]) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut last = 0;
+    for usage in usages {
+        if let Some(replacement) = &usage.notice.replacement {
+            out.push_str(&src[last..usage.span.start]);
+            out.push_str(replacement);
+            last = usage.span.end;
+        }
+    }
+    out.push_str(&src[last..]);
+    out
+}
+
 #[derive(Clone,Debug,Default)]
 pub struct AspectQuery{
     pub all_of: Vec<AspectRid>,
@@ -10,6 +41,28 @@ pub struct AspectQuery{
 }
 
 impl AspectQuery{
+    /// Compiles the text form of a query against a sealed registry.
+    ///
+    /// ```text
+    /// all_of(class.mage) any_of(trait.*) none_of(state.silenced, status.*{1})
+    /// ```
+    ///
+    /// `path.*` expands to every aspect below `path` and `path.*{n}` to those at
+    /// most `n` levels below it. Wildcards are plain shorthand for the expanded
+    /// list, in every clause. A bare pattern means `all_of(pattern)`, and
+    /// clauses may repeat. Unknown paths are reported as
+    /// `AspectError::UnknownAspect` with their span in `src`.
+    pub fn compile(src: &str, registry: &AspectRegistry) -> AspectResult<Self> {
+        parse::QueryParser::new(src, registry).parse()
+    }
+
+    /// Like [`AspectQuery::compile`], also returning the deprecated paths used.
+    pub fn compile_with_usages(src: &str, registry: &AspectRegistry) -> AspectResult<(Self, Vec<DeprecatedUsage>)> {
+        let mut parser = parse::QueryParser::new(src, registry);
+        let query = parser.parse()?;
+        Ok((query, parser.deprecated))
+    }
+
     pub fn normalize(mut self) -> Self {
         self.all_of.sort();
         self.all_of.dedup();
@@ -0,0 +1,132 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::query::AspectQuery;
+use crate::registry::{AspectRegistry, AspectRid};
+use crate::set::AspectSet;
+
+/// A `none_of` aspect found on the entity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Blocker {
+    /// The `none_of` entry that matched.
+    pub rule: AspectRid,
+    /// The most specific aspects of the entity under `rule` (or `rule` itself
+    /// when it was held directly), i.e. why the hierarchy made it match.
+    pub via: Vec<AspectRid>,
+}
+
+/// Why an [`AspectQuery`] did or did not match an aspect set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryExplain {
+    /// `all_of` aspects the entity lacks.
+    pub missing: Vec<AspectRid>,
+    /// `none_of` aspects the entity holds.
+    pub blocked_by: Vec<Blocker>,
+    /// The `any_of` list when none of it is held, empty otherwise.
+    pub any_unsatisfied: Vec<AspectRid>,
+}
+
+impl QueryExplain {
+    pub fn is_match(&self) -> bool {
+        self.missing.is_empty() && self.blocked_by.is_empty() && self.any_unsatisfied.is_empty()
+    }
+
+    /// Renders the reasons with the registry's canonical keys.
+    pub fn display<'a>(&'a self, registry: &'a AspectRegistry) -> ExplainDisplay<'a> {
+        ExplainDisplay { explain: self, registry }
+    }
+}
+
+impl AspectQuery {
+    /// Like [`AspectQuery::matches`], but reports every reason for a mismatch.
+    ///
+    /// `aspects` is expected to be closed under ancestors, as the model stores it.
+    pub fn explain(&self, aspects: &AspectSet, registry: &AspectRegistry) -> QueryExplain {
+        let missing = self.all_of.iter().copied().filter(|a| !aspects.contains(*a)).collect();
+
+        let blocked_by = self
+            .none_of
+            .iter()
+            .copied()
+            .filter(|n| aspects.contains(*n))
+            .map(|rule| Blocker { rule, via: most_specific_under(rule, aspects, registry) })
+            .collect();
+
+        let any_unsatisfied = if self.any_of.iter().any(|a| aspects.contains(*a)) {
+            Vec::new()
+        } else {
+            self.any_of.clone()
+        };
+
+        QueryExplain { missing, blocked_by, any_unsatisfied }
+    }
+}
+
+// Aspects of the set at or below `rule` that have no other such aspect below them.
+fn most_specific_under(rule: AspectRid, aspects: &AspectSet, registry: &AspectRegistry) -> Vec<AspectRid> {
+    let under: Vec<AspectRid> = aspects
+        .as_slice()
+        .iter()
+        .copied()
+        .filter(|a| *a == rule || registry.is_descendant_of(*a, rule))
+        .collect();
+    under
+        .iter()
+        .copied()
+        .filter(|a| !under.iter().any(|b| registry.parent(*b) == Some(*a)))
+        .collect()
+}
+
+pub struct ExplainDisplay<'a> {
+    explain: &'a QueryExplain,
+    registry: &'a AspectRegistry,
+}
+
+impl ExplainDisplay<'_> {
+    fn keys(&self, f: &mut fmt::Formatter<'_>, rids: &[AspectRid]) -> fmt::Result {
+        for (i, rid) in rids.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(self.registry.key(*rid).as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExplainDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let e = self.explain;
+        if e.is_match() {
+            return f.write_str("matched");
+        }
+
+        let mut first = true;
+        let mut line = |f: &mut fmt::Formatter<'_>| {
+            let sep = if first { "" } else { "\n" };
+            first = false;
+            f.write_str(sep)
+        };
+
+        if !e.missing.is_empty() {
+            line(f)?;
+            f.write_str("missing all_of: ")?;
+            self.keys(f, &e.missing)?;
+        }
+        for blocker in &e.blocked_by {
+            line(f)?;
+            write!(f, "blocked by none_of: {}", self.registry.key(blocker.rule).as_str())?;
+            if blocker.via != [blocker.rule] {
+                f.write_str(" (via ")?;
+                self.keys(f, &blocker.via)?;
+                f.write_str(")")?;
+            }
+        }
+        if !e.any_unsatisfied.is_empty() {
+            line(f)?;
+            f.write_str("none of any_of: ")?;
+            self.keys(f, &e.any_unsatisfied)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::{AspectError, AspectResult, Span};
+use crate::expr::AspectExpr;
+use crate::path::AspectPath;
+use crate::query::{AspectQuery, DeprecatedUsage};
+use crate::registry::{AspectRegistry, AspectRid};
+
+/// Single-pass parser for the text forms of [`AspectQuery`] and [`AspectExpr`],
+/// expanding wildcards against the registry as it goes.
+pub(crate) struct QueryParser<'a> {
+    src: &'a str,
+    pos: usize,
+    registry: &'a AspectRegistry,
+    /// Deprecated paths met so far, in source order.
+    pub(crate) deprecated: Vec<DeprecatedUsage>,
+}
+
+#[derive(Clone, Copy)]
+enum Clause {
+    All,
+    Any,
+    None,
+}
+
+impl<'a> QueryParser<'a> {
+    pub(crate) fn new(src: &'a str, registry: &'a AspectRegistry) -> Self {
+        Self { src, pos: 0, registry, deprecated: Vec::new() }
+    }
+
+    pub(crate) fn parse(&mut self) -> AspectResult<AspectQuery> {
+        let mut query = AspectQuery::default();
+        loop {
+            self.skip_separators();
+            if self.pos >= self.src.len() {
+                break;
+            }
+
+            match self.clause()? {
+                Some((Clause::All, rids)) => query.all_of.extend(rids),
+                Some((Clause::Any, rids)) => query.any_of.extend(rids),
+                Some((Clause::None, rids)) => query.none_of.extend(rids),
+                // A bare pattern is shorthand for `all_of(pattern)`.
+                None => self.pattern(&mut query.all_of)?,
+            }
+        }
+        Ok(query.normalize())
+    }
+
+    pub(crate) fn parse_expr(&mut self) -> AspectResult<AspectExpr> {
+        self.skip_whitespace();
+        if self.pos >= self.src.len() {
+            return Ok(AspectExpr::True);
+        }
+        let expr = self.or_expr()?;
+        self.skip_whitespace();
+        if self.pos < self.src.len() {
+            return Err(self.invalid("expected 'and', 'or' or the end of the expression".to_string(), self.char_span()));
+        }
+        Ok(expr.normalize())
+    }
+
+    fn or_expr(&mut self) -> AspectResult<AspectExpr> {
+        let mut terms = vec![self.and_expr()?];
+        while self.keyword("or") {
+            terms.push(self.and_expr()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { AspectExpr::Or(terms) })
+    }
+
+    fn and_expr(&mut self) -> AspectResult<AspectExpr> {
+        let mut terms = vec![self.unary_expr()?];
+        while self.keyword("and") {
+            terms.push(self.unary_expr()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { AspectExpr::And(terms) })
+    }
+
+    fn unary_expr(&mut self) -> AspectResult<AspectExpr> {
+        if self.keyword("not") {
+            return Ok(!self.unary_expr()?);
+        }
+        self.skip_whitespace();
+        if self.peek() == Some(b'(') {
+            let open = self.pos;
+            self.pos += 1;
+            let inner = self.or_expr()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b')') {
+                return Err(self.invalid("unclosed '('".to_string(), Span::new(open, open + 1)));
+            }
+            self.pos += 1;
+            return Ok(inner);
+        }
+
+        let has = |rids: Vec<AspectRid>| rids.into_iter().map(AspectExpr::Has).collect::<Vec<_>>();
+        Ok(match self.clause()? {
+            Some((Clause::All, rids)) => AspectExpr::And(has(rids)),
+            Some((Clause::Any, rids)) => AspectExpr::Or(has(rids)),
+            Some((Clause::None, rids)) => !AspectExpr::Or(has(rids)),
+            None => {
+                let mut rids = Vec::new();
+                self.pattern(&mut rids)?;
+                AspectExpr::Or(has(rids))
+            }
+        })
+    }
+
+    /// Consumes `kw` if it comes next as a whole word.
+    fn keyword(&mut self, kw: &str) -> bool {
+        self.skip_whitespace();
+        let rest = &self.src.as_bytes()[self.pos..];
+        let boundary = rest.get(kw.len()).is_none_or(|b| !is_path_byte(*b));
+        if rest.starts_with(kw.as_bytes()) && boundary {
+            self.pos += kw.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses `all_of(...)`, `any_of(...)` or `none_of(...)` if one comes next.
+    fn clause(&mut self) -> AspectResult<Option<(Clause, Vec<AspectRid>)>> {
+        let start = self.pos;
+        let word = self.scan_path();
+        let after_word = self.pos;
+        self.skip_whitespace();
+        if self.peek() != Some(b'(') {
+            self.pos = start;
+            return Ok(None);
+        }
+        let clause = match word {
+            "all_of" => Clause::All,
+            "any_of" => Clause::Any,
+            "none_of" => Clause::None,
+            _ => {
+                return Err(self.invalid(
+                    format!("unknown clause '{word}', expected all_of, any_of or none_of"),
+                    Span::new(start, after_word.max(start + 1)),
+                ));
+            }
+        };
+        let mut rids = Vec::new();
+        self.clause_body(&mut rids)?;
+        Ok(Some((clause, rids)))
+    }
+
+    fn clause_body(&mut self, out: &mut Vec<AspectRid>) -> AspectResult<()> {
+        let open = self.pos;
+        self.pos += 1;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b')') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                None => return Err(self.invalid("unclosed '('".to_string(), Span::new(open, open + 1))),
+                _ => {}
+            }
+            self.pattern(out)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b')') => {}
+                None => return Err(self.invalid("unclosed '('".to_string(), Span::new(open, open + 1))),
+                _ => return Err(self.invalid("expected ',' or ')'".to_string(), self.char_span())),
+            }
+        }
+    }
+
+    /// `path`, `path.*` (every aspect below) or `path.*{n}` (at most n levels below).
+    fn pattern(&mut self, out: &mut Vec<AspectRid>) -> AspectResult<()> {
+        let start = self.pos;
+        let text = self.scan_path();
+        if text.is_empty() {
+            return Err(self.invalid("expected an aspect path".to_string(), self.char_span()));
+        }
+
+        if self.peek() != Some(b'*') {
+            let span = Span::new(start, self.pos);
+            out.push(self.resolve(text, span)?);
+            return Ok(());
+        }
+
+        let Some(base) = text.strip_suffix('.') else {
+            return Err(self.invalid("wildcard must follow a '.'".to_string(), self.char_span()));
+        };
+        let base_span = Span::new(start, start + base.len());
+        self.pos += 1;
+
+        let mut max_depth = None;
+        if self.peek() == Some(b'{') {
+            let open = self.pos;
+            self.pos += 1;
+            let digits_start = self.pos;
+            while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                self.pos += 1;
+            }
+            let digits = &self.src[digits_start..self.pos];
+            if self.peek() != Some(b'}') {
+                return Err(self.invalid("expected '}' after wildcard depth".to_string(), self.char_span()));
+            }
+            self.pos += 1;
+            match digits.parse::<u16>() {
+                Ok(depth) if depth > 0 => max_depth = Some(depth),
+                _ => {
+                    return Err(self.invalid(
+                        "wildcard depth must be a positive integer".to_string(),
+                        Span::new(open, self.pos),
+                    ));
+                }
+            }
+        }
+
+        let rid = self.resolve(base, base_span)?;
+        let mut expanded = Vec::new();
+        self.registry.descendants(rid, max_depth, &mut expanded);
+        if expanded.is_empty() {
+            let text = &self.src[start..self.pos];
+            return Err(self.invalid(format!("'{text}' matches no aspects"), Span::new(start, self.pos)));
+        }
+        out.extend(expanded);
+        Ok(())
+    }
+
+    fn resolve(&mut self, text: &str, span: Span) -> AspectResult<AspectRid> {
+        let path = AspectPath::parse(text)
+            .map_err(|_| self.invalid(format!("invalid aspect path '{text}'"), span))?;
+        let resolution = self.registry.resolve(path.as_str()).ok_or_else(|| AspectError::UnknownAspect {
+            path: path.as_str().to_string(),
+            span: Some(span),
+        })?;
+        if let Some(notice) = resolution.deprecated {
+            self.deprecated.push(DeprecatedUsage { span, notice });
+        }
+        Ok(resolution.rid)
+    }
+
+    fn scan_path(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(is_path_byte) {
+            self.pos += 1;
+        }
+        &self.src[start..self.pos]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_separators(&mut self) {
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b',') {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Span of the character at the cursor, or an empty span at the end.
+    fn char_span(&self) -> Span {
+        let len = self.src[self.pos..].chars().next().map_or(0, char::len_utf8);
+        Span::new(self.pos, self.pos + len)
+    }
+
+    fn invalid(&self, message: String, span: Span) -> AspectError {
+        AspectError::InvalidQuery { message, span: Some(span) }
+    }
+}
+
+fn is_path_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.')
+}
@@ -1,16 +1,24 @@
 use alloc::string::{String, ToString};
+use alloc::format;
+use alloc::boxed::Box;
 use alloc::vec::Vec;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 
-use wmms_core::hash::hash_str128;
+use wmms_core::hash::{StableHash, StableHasher, hash_str128};
 use wmms_core::prelude::*;
 
+use crate::alias::{AspectAlias, Deprecation, DeprecationNotice, Resolution};
+use crate::diff::RegistryDiff;
 use crate::error::{AspectError, AspectResult};
+use crate::export::TreeExport;
+use crate::layer::AspectLayer;
+use crate::meta::AspectMeta;
 use crate::path::AspectPath;
+use crate::policy::TaxonomyPolicy;
 use crate::set::AspectSet;
 
 #[repr(transparent)]
-#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,StableHash)]
 pub struct AspectRid(pub u32);
 
 #[derive(Clone,Debug)]
@@ -21,28 +29,65 @@ pub struct AspectNode{
     pub parent: Option<AspectRid>,
     pub children: Vec<AspectRid>,
     pub depth: u16,
+    pub deprecation: Option<Deprecation>,
+    pub meta: AspectMeta,
+    /// Set when the subtrees under this node are mutually exclusive: an
+    /// aspect set may hold at most one of its children (with descendants).
+    pub exclusive: bool,
 }
 
+/// Sealed aspect taxonomy.
+///
+/// `registry_hash` covers the node tree only: declaring aliases, deprecations,
+/// metadata or exclusive groups does not change it. For a layered registry it
+/// combines the hashes of every layer.
+#[derive(Clone)]
 pub struct AspectRegistry{
     nodes: Vec<AspectNode>,
     by_key: BTreeMap<CanonicalKey, AspectRid>,
     by_id: BTreeMap<AspectId, AspectRid>,
+    aliases: BTreeMap<CanonicalKey, AspectAlias>,
+    layers: Vec<AspectLayer>,
     pub registry_hash: Hash128,
 }
 
+#[derive(Default)]
 pub struct AspectRegistryBuilder{
+    base: Option<AspectRegistry>,
+    layer: Option<String>,
     keys: BTreeMap<CanonicalKey, ()>,
+    aliases: BTreeMap<CanonicalKey, (CanonicalKey, Option<Deprecation>)>,
+    deprecations: BTreeMap<CanonicalKey, Deprecation>,
+    meta: BTreeMap<CanonicalKey, AspectMeta>,
+    exclusive: BTreeMap<CanonicalKey, ()>,
+    policy: Option<TaxonomyPolicy>,
     sealed: bool,
 }
 
 impl AspectRegistryBuilder{
     pub fn new()->Self{
-        Self{
-            keys: BTreeMap::new(),
-            sealed: false,
+        Self::default()
+    }
+
+    /// Starts a layer named `name` on top of `base`.
+    ///
+    /// Base RIDs are kept and new nodes are numbered after them. The layer may
+    /// add nodes under base nodes and alias to them, but not deprecate,
+    /// describe or regroup anything the base declared.
+    pub fn extending(base: &AspectRegistry, name: &str) -> Self {
+        Self {
+            base: Some(base.clone()),
+            layer: Some(name.to_string()),
+            ..Self::default()
         }
     }
 
+    /// Enforces `policy` on every path and alias when sealing.
+    pub fn with_policy(mut self, policy: TaxonomyPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
     pub fn register(&mut self, path: &str)->AspectResult<()>{
         if self.sealed {
             return Err(AspectError::Sealed);
@@ -53,6 +98,64 @@ impl AspectRegistryBuilder{
         Ok(())
     }
 
+    /// Declares `from` as another name for `to`, without a diagnostic.
+    ///
+    /// `to` may itself be an alias. `from` must not be a registered path.
+    pub fn alias(&mut self, from: &str, to: &str) -> AspectResult<()> {
+        self.insert_alias(from, to, None)
+    }
+
+    /// Registers `to` and keeps `from` resolving to it as a deprecated alias,
+    /// so `AspectId`s and saved paths using the old name stay valid.
+    pub fn rename(&mut self, from: &str, to: &str) -> AspectResult<()> {
+        self.register(to)?;
+        self.insert_alias(from, to, Some(Deprecation::default()))
+    }
+
+    /// Marks a registered path or an alias as deprecated.
+    pub fn deprecate(&mut self, path: &str, note: Option<&str>) -> AspectResult<()> {
+        if self.sealed {
+            return Err(AspectError::Sealed);
+        }
+        let key = AspectPath::parse(path)?.key().clone();
+        self.deprecations.insert(key, Deprecation { note: note.map(str::to_string) });
+        Ok(())
+    }
+
+    /// Attaches authoring metadata to a registered path, replacing any earlier one.
+    pub fn describe(&mut self, path: &str, meta: AspectMeta) -> AspectResult<()> {
+        if self.sealed {
+            return Err(AspectError::Sealed);
+        }
+        let key = AspectPath::parse(path)?.key().clone();
+        self.meta.insert(key, meta);
+        Ok(())
+    }
+
+    /// Declares the children of `path` mutually exclusive, e.g. `state.stance`
+    /// so that an entity holds only one `state.stance.*` at a time.
+    pub fn exclusive(&mut self, path: &str) -> AspectResult<()> {
+        if self.sealed {
+            return Err(AspectError::Sealed);
+        }
+        let key = AspectPath::parse(path)?.key().clone();
+        self.exclusive.insert(key, ());
+        Ok(())
+    }
+
+    fn insert_alias(&mut self, from: &str, to: &str, deprecation: Option<Deprecation>) -> AspectResult<()> {
+        if self.sealed {
+            return Err(AspectError::Sealed);
+        }
+        let from = AspectPath::parse(from)?.key().clone();
+        let to = AspectPath::parse(to)?.key().clone();
+        if self.aliases.contains_key(&from) {
+            return Err(AspectError::Duplicate(from.as_str().to_string()));
+        }
+        self.aliases.insert(from, (to, deprecation));
+        Ok(())
+    }
+
     fn insert_with_ancestors(&mut self, canonical: &str) -> AspectResult<()> {
         self.keys.entry(CanonicalKey::from_dotted_ident(canonical)?).or_insert(());
         let mut cur = AspectPath::parse(canonical)?;
@@ -67,13 +170,39 @@ impl AspectRegistryBuilder{
     pub fn seal(mut self) -> AspectResult<AspectRegistry>{
         self.sealed = true;
 
-        // Assign RIDs in canonical order by path (BTreeMap order)
-        let mut nodes: Vec<AspectNode> = Vec::with_capacity(self.keys.len());
-        let mut by_key: BTreeMap<CanonicalKey, AspectRid> = BTreeMap::new();
-        let mut by_id: BTreeMap<AspectId, AspectRid> = BTreeMap::new();
+        let base = self.base.take();
+        let base_alias_keys: BTreeSet<CanonicalKey> =
+            base.iter().flat_map(|b| b.aliases.keys().cloned()).collect();
+
+        if let Some(policy) = &self.policy {
+            // Base paths take part in collision checks, but only this layer's
+            // paths are reported.
+            let base_paths = base.iter().flat_map(|b| b.by_key.keys()).chain(&base_alias_keys);
+            let paths = base_paths.chain(self.keys.keys()).chain(self.aliases.keys()).map(CanonicalKey::as_str);
+            let mut violations = policy.check_paths(paths);
+            if let Some(b) = &base {
+                violations.retain(|v| {
+                    CanonicalKey::from_dotted_ident(&v.path)
+                        .is_ok_and(|k| !b.by_key.contains_key(&k) && !base_alias_keys.contains(&k))
+                });
+            }
+            if !violations.is_empty() {
+                return Err(AspectError::Policy { violations });
+            }
+        }
+
+        let (mut nodes, mut by_key, mut by_id, mut aliases, mut layers) = match base {
+            Some(b) => (b.nodes, b.by_key, b.by_id, b.aliases, b.layers),
+            None => Default::default(),
+        };
+        let start = nodes.len();
 
-        for(i, (key,_)) in self.keys.iter().enumerate() {
-            let rid = AspectRid(i as u32);
+        // Assign RIDs in canonical order by path (BTreeMap order), after the base layers
+        for key in self.keys.keys() {
+            if by_key.contains_key(key) {
+                continue;
+            }
+            let rid = AspectRid(nodes.len() as u32);
             let id = AspectId::new(key.as_str()); // Stable hash from canonical path
             nodes.push(AspectNode {
                 rid, id,
@@ -81,37 +210,40 @@ impl AspectRegistryBuilder{
                 parent: None,
                 children: Vec::new(),
                 depth: 0,
+                deprecation: None,
+                meta: AspectMeta::default(),
+                exclusive: false,
             });
             by_key.insert(key.clone(), rid);
             by_id.insert(id, rid);
         }
 
         // Set up parent/child relationships and depths
-        for idx in 0..nodes.len() {
-            let _key = nodes[idx].key.clone();
-            let ap = AspectPath::parse(nodes[idx].key.as_str())?;
+        for node in nodes[start..].iter_mut() {
+            let ap = AspectPath::parse(node.key.as_str())?;
             if let Some(parent) = ap.parent(){
                 let parent_rid = *by_key.get(parent.key())
-                .ok_or_else(|| AspectError::UnknownAspect(parent.as_str().to_string()))?;
-                nodes[idx].parent = Some(parent_rid);
+                .ok_or_else(|| AspectError::UnknownAspect { path: parent.as_str().to_string(), span: None })?;
+                node.parent = Some(parent_rid);
             }
         }
 
         // children lists
-        for idx in 0..nodes.len() {
+        for idx in start..nodes.len() {
             let (maybe_parent, rid) = (nodes[idx].parent, nodes[idx].rid);
             if let Some(p) = maybe_parent {
                 nodes[p.0 as usize].children.push(rid);
             }
         }
 
-        // sort children by canonical order (RIDs are assigned in canonical path order)
-        for idx in 0..nodes.len() {
-            nodes[idx].children.sort_by_key(|c| c.0);
+        // sort children by canonical order (layers break RID order under base nodes)
+        let keys: Vec<CanonicalKey> = nodes.iter().map(|n| n.key.clone()).collect();
+        for node in nodes.iter_mut() {
+            node.children.sort_by(|a, b| keys[a.0 as usize].cmp(&keys[b.0 as usize]));
         }
 
         // compute depth deterministically (walk parents)
-        for idx in 0..nodes.len() {
+        for idx in start..nodes.len() {
             let mut d: u16 = 0;
             let mut cur = nodes[idx].parent;
             while let Some(p) = cur {
@@ -121,21 +253,87 @@ impl AspectRegistryBuilder{
             nodes[idx].depth = d;
         }
 
-        // registry hash
+        // layer hash, then the registry hash over all layers
         let mut acc = String::new();
-        for n in &nodes {
-            acc.push_str(&n.key.as_str());
+        for n in &nodes[start..] {
+            acc.push_str(n.key.as_str());
             acc.push('|');
             if let Some(p) = n.parent {
-                acc.push_str(&nodes[p.0 as usize].key.as_str());
+                acc.push_str(nodes[p.0 as usize].key.as_str());
             }
             acc.push('\n');
         }
-        let registry_hash = hash_str128(&acc);
+        layers.push(AspectLayer {
+            name: self.layer.take().unwrap_or_else(|| "base".to_string()),
+            start: AspectRid(start as u32),
+            len: (nodes.len() - start) as u32,
+            hash: hash_str128(&acc),
+        });
+        let registry_hash = if let [only] = layers.as_slice() {
+            only.hash
+        } else {
+            let mut h = StableHasher::with_domain("wmms.aspects.layers");
+            for layer in &layers {
+                layer.hash.stable_hash(&mut h);
+            }
+            h.finish()
+        };
+
+        // aliases: follow alias chains to a node (or a base alias), rejecting cycles
+        for (from, (_, deprecation)) in &self.aliases {
+            if by_key.contains_key(from) || base_alias_keys.contains(from) {
+                return Err(AspectError::Duplicate(from.as_str().to_string()));
+            }
+            let mut target = &self.aliases[from].0;
+            let mut hops = 0;
+            while let Some((next, _)) = self.aliases.get(target) {
+                hops += 1;
+                if hops > self.aliases.len() {
+                    return Err(AspectError::InvalidPath(format!("alias cycle through '{}'", from.as_str())));
+                }
+                target = next;
+            }
+            let rid = *by_key.get(target).or_else(|| aliases.get(target).map(|a| &a.target)).ok_or_else(|| AspectError::UnknownAspect {
+                path: target.as_str().to_string(),
+                span: None,
+            })?;
+            by_id.entry(AspectId::new(from.as_str())).or_insert(rid);
+            aliases.insert(from.clone(), AspectAlias { target: rid, deprecation: deprecation.clone() });
+        }
+
+        let is_base = |path: &CanonicalKey| {
+            base_alias_keys.contains(path) || by_key.get(path).is_some_and(|rid| (rid.0 as usize) < start)
+        };
+        let declared = self.deprecations.keys().chain(self.meta.keys()).chain(self.exclusive.keys());
+        if let Some(path) = declared.into_iter().find(|p| is_base(p)) {
+            return Err(AspectError::BaseLayer(path.as_str().to_string()));
+        }
+
+        for (path, deprecation) in self.deprecations {
+            if let Some(alias) = aliases.get_mut(&path) {
+                alias.deprecation = Some(deprecation);
+            } else if let Some(rid) = by_key.get(&path) {
+                nodes[rid.0 as usize].deprecation = Some(deprecation);
+            } else {
+                return Err(AspectError::UnknownAspect { path: path.as_str().to_string(), span: None });
+            }
+        }
+
+        for (path, meta) in self.meta {
+            let rid = *by_key.get(&path).ok_or_else(|| AspectError::UnknownAspect { path: path.as_str().to_string(), span: None })?;
+            nodes[rid.0 as usize].meta = meta;
+        }
+        for path in self.exclusive.into_keys() {
+            let rid = *by_key.get(&path).ok_or_else(|| AspectError::UnknownAspect { path: path.as_str().to_string(), span: None })?;
+            nodes[rid.0 as usize].exclusive = true;
+        }
+
         Ok(AspectRegistry{
             nodes,
             by_key,
             by_id,
+            aliases,
+            layers,
             registry_hash,
         })
     }
@@ -148,10 +346,154 @@ impl AspectRegistry{
     pub fn is_empty(&self) -> bool{
         self.nodes.is_empty()
     }
+    /// Resolves a path, following aliases silently.
     pub fn resolve_path(&self, path: &str) -> Option<AspectRid>{
+        self.resolve(path).map(|r| r.rid)
+    }
+
+    /// Resolves a path, following aliases (including aliased ancestors) and
+    /// reporting a deprecation notice when a deprecated name was used.
+    pub fn resolve(&self, path: &str) -> Option<Resolution>{
         let p = AspectPath::parse(path).ok()?;
-        self.by_key.get(p.key()).copied()
+        if let Some(&rid) = self.by_key.get(p.key()) {
+            let deprecated = self.node(rid).deprecation.as_ref().map(|d| DeprecationNotice {
+                path: p.as_str().to_string(),
+                replacement: None,
+                note: d.note.clone(),
+            });
+            return Some(Resolution { rid, deprecated });
+        }
+
+        // Longest aliased prefix, then the rest of the path under its target.
+        let mut prefix = Some(p.clone());
+        while let Some(cur) = prefix {
+            if let Some(alias) = self.aliases.get(cur.key()) {
+                let rest = &p.as_str()[cur.as_str().len()..];
+                let rid = if rest.is_empty() {
+                    alias.target
+                } else {
+                    let target = format!("{}{rest}", self.key(alias.target).as_str());
+                    *self.by_key.get(&CanonicalKey::from_dotted_ident(&target).ok()?)?
+                };
+                let deprecation = alias.deprecation.as_ref().or(self.node(rid).deprecation.as_ref());
+                let deprecated = deprecation.map(|d| DeprecationNotice {
+                    path: p.as_str().to_string(),
+                    replacement: Some(self.key(rid).as_str().to_string()),
+                    note: d.note.clone(),
+                });
+                return Some(Resolution { rid, deprecated });
+            }
+            prefix = cur.parent();
+        }
+        None
+    }
+
+    /// Declared aliases in canonical order.
+    pub fn aliases(&self) -> impl Iterator<Item = (&CanonicalKey, &AspectAlias)> + '_ {
+        self.aliases.iter()
+    }
+
+    /// Deprecated node paths and aliases in canonical order, for tooling
+    /// that lists or rewrites their usages.
+    pub fn deprecated(&self) -> impl Iterator<Item = (&CanonicalKey, &Deprecation)> + '_ {
+        let mut all: Vec<(&CanonicalKey, &Deprecation)> = self
+            .nodes
+            .iter()
+            .filter_map(|n| n.deprecation.as_ref().map(|d| (&n.key, d)))
+            .chain(self.aliases.iter().filter_map(|(k, a)| a.deprecation.as_ref().map(|d| (k, d))))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(b.0));
+        all.into_iter()
+    }
+    /// Nodes in RID order: layer by layer, in canonical path order within each.
+    ///
+    /// Registering every key of each layer again rebuilds an identical
+    /// registry, so saves can store the key lists to diff against a later
+    /// taxonomy. Parents always come before their children.
+    pub fn nodes(&self) -> impl Iterator<Item = &AspectNode> + '_ {
+        self.nodes.iter()
+    }
+
+    /// Exact lookup of a node path, ignoring aliases.
+    pub fn rid_of_key(&self, key: &CanonicalKey) -> Option<AspectRid>{
+        self.by_key.get(key).copied()
+    }
+
+    /// Compares this registry, taken as the older one, to `to`.
+    pub fn diff(&self, to: &AspectRegistry) -> RegistryDiff{
+        RegistryDiff::between(self, to)
+    }
+
+    /// Checks that data written against `stored` can be used with this
+    /// registry as is, reporting the full diff when the hashes differ.
+    pub fn verify(&self, stored: &AspectRegistry) -> AspectResult<()>{
+        if self.registry_hash == stored.registry_hash {
+            return Ok(());
+        }
+        Err(AspectError::RegistryMismatch(Box::new(stored.diff(self))))
+    }
+    /// Top-level nodes in canonical path order.
+    pub fn roots(&self) -> impl Iterator<Item = AspectRid> + '_ {
+        let mut roots: Vec<&AspectNode> = self.nodes.iter().filter(|n| n.parent.is_none()).collect();
+        roots.sort_by(|a, b| a.key.cmp(&b.key));
+        roots.into_iter().map(|n| n.rid)
+    }
+
+    /// Exporter for the node tree, see [`TreeExport`].
+    pub fn export(&self) -> TreeExport<'_>{
+        TreeExport::new(self)
+    }
+
+    /// Layers from the base up.
+    pub fn layers(&self) -> &[AspectLayer]{
+        &self.layers
+    }
+
+    pub fn layer_of(&self, rid: AspectRid) -> &AspectLayer{
+        self.layers.iter().find(|l| l.contains(rid)).expect("rid out of range")
+    }
+
+    pub fn meta(&self, rid: AspectRid) -> &AspectMeta{
+        &self.nodes[rid.0 as usize].meta
+    }
+
+    /// Nodes whose children are mutually exclusive, in RID order.
+    pub fn exclusive_groups(&self) -> impl Iterator<Item = AspectRid> + '_ {
+        self.nodes.iter().filter(|n| n.exclusive).map(|n| n.rid)
     }
+
+    /// Checks that `rids` hold at most one branch of every exclusive group.
+    ///
+    /// The first conflict is reported with its two paths in canonical order.
+    pub fn check_exclusive(&self, rids: &[AspectRid]) -> AspectResult<()>{
+        // exclusive group -> (branch child, aspect that selected it)
+        let mut chosen: BTreeMap<AspectRid, (AspectRid, AspectRid)> = BTreeMap::new();
+        let mut sorted = rids.to_vec();
+        sorted.sort();
+        for rid in sorted {
+            let mut branch = rid;
+            while let Some(group) = self.parent(branch) {
+                if self.node(group).exclusive {
+                    match chosen.get(&group) {
+                        Some(&(other, by)) if other != branch => {
+                            return Err(AspectError::Exclusive {
+                                group: self.key(group).as_str().to_string(),
+                                first: self.key(by).as_str().to_string(),
+                                second: self.key(rid).as_str().to_string(),
+                            });
+                        }
+                        Some(_) => {}
+                        None => {
+                            chosen.insert(group, (branch, rid));
+                        }
+                    }
+                }
+                branch = group;
+            }
+        }
+        Ok(())
+    }
+
     pub fn resolve_id(&self, id: &AspectId) -> Option<AspectRid>{
         self.by_id.get(id).copied()
     }
@@ -187,6 +529,20 @@ impl AspectRegistry{
         }
     }
 
+    /// Collects the aspects below `rid` in depth-first canonical order, down to
+    /// `max_depth` levels below it (unbounded if `None`).
+    pub fn descendants(&self, rid: AspectRid, max_depth: Option<u16>, out: &mut Vec<AspectRid>){
+        out.clear();
+        let base = self.node(rid).depth;
+        let mut stack: Vec<AspectRid> = self.children(rid).iter().rev().copied().collect();
+        while let Some(cur) = stack.pop() {
+            out.push(cur);
+            if max_depth.is_none_or(|max| self.node(cur).depth - base < max) {
+                stack.extend(self.children(cur).iter().rev().copied());
+            }
+        }
+    }
+
     pub fn close_under_ancestors(&self, base: &[AspectRid]) -> AspectSet {
         let mut result = AspectSet::new();
         for &rid in base {
@@ -1,7 +1,9 @@
 use alloc::vec::Vec;
+use wmms_core::hash::StableHash;
 use crate::registry::{AspectRid};
 
-#[derive(Clone,Debug,Default, PartialEq)]
+/// Rids are kept sorted and deduplicated, so the stable hash is canonical.
+#[derive(Clone,Debug,Default, PartialEq,StableHash)]
 pub struct AspectSet{
     rids: Vec<AspectRid>,
 }
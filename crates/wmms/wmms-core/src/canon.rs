@@ -1,3 +1,5 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use crate::error::{Result, WMMSCoreError};
 
 #[derive(Clone,PartialEq,Eq,PartialOrd,Ord,Hash,Debug)]
@@ -47,8 +49,8 @@ impl CanonicalKey {
 }
 
 // Canonical BTree Key Container
-pub type CanonMap<K,V> = std::collections::BTreeMap<K, V>;
-pub type CanonSet<K> = std::collections::BTreeSet<K>;
+pub type CanonMap<K,V> = alloc::collections::BTreeMap<K, V>;
+pub type CanonSet<K> = alloc::collections::BTreeSet<K>;
 
 pub fn canon_sort<T: Ord>(v: &mut [T]) {
     v.sort();
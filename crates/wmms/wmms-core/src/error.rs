@@ -1,7 +1,9 @@
+use alloc::string::String;
 
-pub type Result<T> = std::result::Result<T, WMMSCoreError>;
+pub type Result<T> = core::result::Result<T, WMMSCoreError>;
 
-#[derive(thiserror::Error, Debug, miette::Diagnostic)]
+#[derive(thiserror::Error, Debug)]
+#[cfg_attr(feature = "std", derive(miette::Diagnostic))]
 pub enum WMMSCoreError {
     #[error("Invalid canonical path: {0}")]
     InvalidPath(String),
@@ -9,6 +11,9 @@ pub enum WMMSCoreError {
     #[error("Id collision for {prefix}: '{a}' with '{b}'")]
     IdCollision { prefix: &'static str, a: String, b: String },
 
+    #[error("Invalid date: {0}")]
+    InvalidDate(String),
+
     #[error("Tick overflow")]
     TickOverflow,
 
@@ -1,3 +1,10 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::canon::{CanonMap, CanonSet, CanonicalKey};
+use crate::ids::EntityId;
+use crate::num::{Decimal, FixedI64, FixedU32};
+use crate::time::{Tick, TickDelta};
 
 #[repr(transparent)]
 #[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
@@ -11,6 +18,11 @@ impl Hash128 {
     pub fn as_u128(&self) -> u128 {
         self.0
     }
+
+    #[inline]
+    pub fn raw(&self) -> u128 {
+        self.0
+    }
     
 }
 
@@ -26,6 +38,11 @@ impl Hash64 {
     pub fn as_u64(&self) -> u64 {
         self.0
     }
+
+    #[inline]
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
     
 }
 
@@ -51,4 +68,319 @@ pub fn hash_str128(s: &str) -> Hash128 {
 
 pub fn hash_str64(s: &str) -> Hash64 {
     hash64(s.as_bytes())
-}
\ No newline at end of file
+}
+
+/// Incremental blake3 hasher with a fixed, platform-independent encoding.
+///
+/// Integers are written little-endian at their declared width, and strings,
+/// byte slices and collections are length-prefixed, so two values only share a
+/// digest if they have the same structure. Used through [`StableHash`].
+#[derive(Clone, Debug, Default)]
+pub struct StableHasher {
+    inner: blake3::Hasher,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        Self { inner: blake3::Hasher::new() }
+    }
+
+    /// Hasher whose digests cannot collide with those of another `domain`.
+    pub fn with_domain(domain: &str) -> Self {
+        let mut h = Self::new();
+        h.write_str(domain);
+        h
+    }
+
+    #[inline]
+    pub fn write_u8(&mut self, v: u8) {
+        self.inner.update(&[v]);
+    }
+
+    #[inline]
+    pub fn write_u16(&mut self, v: u16) {
+        self.inner.update(&v.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u32(&mut self, v: u32) {
+        self.inner.update(&v.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u64(&mut self, v: u64) {
+        self.inner.update(&v.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u128(&mut self, v: u128) {
+        self.inner.update(&v.to_le_bytes());
+    }
+
+    /// Lengths are always written as `u64`, whatever the pointer width.
+    #[inline]
+    pub fn write_len(&mut self, len: usize) {
+        self.write_u64(len as u64);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_len(bytes.len());
+        self.inner.update(bytes);
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    pub fn finish(&self) -> Hash128 {
+        let hash = self.inner.finalize();
+        let mut arr = [0u8; 16];
+        arr.copy_from_slice(&hash.as_bytes()[0..16]);
+        Hash128(u128::from_le_bytes(arr))
+    }
+
+    pub fn finish64(&self) -> Hash64 {
+        Hash64(self.finish().0 as u64)
+    }
+}
+
+/// Structural hash that only depends on a value's logical content.
+///
+/// Unlike `core::hash::Hash`, the encoding is fixed across platforms, builds and
+/// runs, so digests can be compared between peers or stored with a replay.
+/// Derive it with `#[derive(StableHash)]`; fields marked
+/// `#[stable_hash(skip)]` are left out.
+pub trait StableHash {
+    fn stable_hash(&self, hasher: &mut StableHasher);
+
+    /// Digest of this value alone.
+    fn stable_digest(&self) -> Hash128 {
+        let mut h = StableHasher::new();
+        self.stable_hash(&mut h);
+        h.finish()
+    }
+}
+
+pub use wmms_derive::StableHash;
+
+macro_rules! stable_hash_int {
+    ($($ty:ty => $write:ident as $as:ty),* $(,)?) => {
+        $(
+            impl StableHash for $ty {
+                #[inline]
+                fn stable_hash(&self, hasher: &mut StableHasher) {
+                    hasher.$write(*self as $as);
+                }
+            }
+        )*
+    };
+}
+
+stable_hash_int! {
+    u8 => write_u8 as u8,
+    u16 => write_u16 as u16,
+    u32 => write_u32 as u32,
+    u64 => write_u64 as u64,
+    u128 => write_u128 as u128,
+    usize => write_u64 as u64,
+    i8 => write_u8 as u8,
+    i16 => write_u16 as u16,
+    i32 => write_u32 as u32,
+    i64 => write_u64 as u64,
+    i128 => write_u128 as u128,
+    isize => write_u64 as u64,
+}
+
+impl StableHash for bool {
+    #[inline]
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u8(*self as u8);
+    }
+}
+
+/// Hashes the bit pattern, with every NaN and `-0.0` folded to one encoding.
+impl StableHash for f32 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let bits = if self.is_nan() {
+            f32::NAN.to_bits()
+        } else if *self == 0.0 {
+            0
+        } else {
+            self.to_bits()
+        };
+        hasher.write_u32(bits);
+    }
+}
+
+impl StableHash for f64 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let bits = if self.is_nan() {
+            f64::NAN.to_bits()
+        } else if *self == 0.0 {
+            0
+        } else {
+            self.to_bits()
+        };
+        hasher.write_u64(bits);
+    }
+}
+
+impl StableHash for str {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self);
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Box<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            None => hasher.write_u8(0),
+            Some(v) => {
+                hasher.write_u8(1);
+                v.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_len(self.len());
+        for v in self {
+            v.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+/// `CanonMap` iterates in key order, so the digest is independent of insertion order.
+impl<K: StableHash, V: StableHash> StableHash for CanonMap<K, V> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_len(self.len());
+        for (k, v) in self {
+            k.stable_hash(hasher);
+            v.stable_hash(hasher);
+        }
+    }
+}
+
+impl<K: StableHash> StableHash for CanonSet<K> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_len(self.len());
+        for k in self {
+            k.stable_hash(hasher);
+        }
+    }
+}
+
+macro_rules! stable_hash_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: StableHash),+> StableHash for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                let ($($name,)+) = self;
+                $($name.stable_hash(hasher);)+
+            }
+        }
+    };
+}
+
+stable_hash_tuple!(A);
+stable_hash_tuple!(A B);
+stable_hash_tuple!(A B C);
+stable_hash_tuple!(A B C D);
+
+impl StableHash for Hash64 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.0);
+    }
+}
+
+impl StableHash for Hash128 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u128(self.0);
+    }
+}
+
+impl StableHash for CanonicalKey {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self.as_str());
+    }
+}
+
+impl StableHash for Tick {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.0);
+    }
+}
+
+impl StableHash for TickDelta {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.0);
+    }
+}
+
+impl<const FRAC_BITS: u32> StableHash for FixedU32<FRAC_BITS> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u32(self.0 as u32);
+    }
+}
+
+impl<const FRAC_BITS: u32> StableHash for FixedI64<FRAC_BITS> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.0 as u64);
+    }
+}
+
+/// Numerically equal decimals (`1.5` and `1.50`) hash alike, matching `Eq`.
+impl StableHash for Decimal {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let n = self.normalize();
+        hasher.write_u128(n.mantissa() as u128);
+        hasher.write_u32(n.scale());
+    }
+}
+
+impl StableHash for EntityId {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            EntityId::Auth(id) => {
+                hasher.write_u8(0);
+                id.stable_hash(hasher);
+            }
+            EntityId::Run(id) => {
+                hasher.write_u8(1);
+                id.stable_hash(hasher);
+            }
+        }
+    }
+}
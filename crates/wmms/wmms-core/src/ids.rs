@@ -1,6 +1,19 @@
-use std::{fmt::{Display, Formatter}, ops::{ Shr, BitAnd}};
+use alloc::format;
+use core::fmt::{Display, Formatter};
 
-use crate::hash::{Hash128, Hash64, hash_str128, hash_str64};
+use crate::error::{Result, WMMSCoreError};
+#[cfg(feature = "id64")]
+use crate::hash::{Hash64, hash_str64};
+#[cfg(feature = "id128")]
+use crate::hash::{Hash128, hash_str128};
+
+pub mod migrate;
+
+#[cfg(all(feature = "id64", feature = "id128"))]
+compile_error!("wmms-core: the `id64` and `id128` features are mutually exclusive, enable only one");
+
+#[cfg(not(any(feature = "id64", feature = "id128")))]
+compile_error!("wmms-core: enable exactly one ID width feature, `id64` or `id128`");
 
 #[cfg(feature = "id64")]
 pub type IdSize = u64;
@@ -19,6 +32,131 @@ pub trait IdPrefix{
     const PREFIX: &'static str;
 }
 
+/// An ID minted by hashing `prefix:canonical`, usable generically by the interner.
+pub trait CanonicalId: IdPrefix + Copy + Ord {
+    fn mint(canonical: &str) -> Self;
+    fn raw(&self) -> IdSize;
+    fn from_raw(raw: IdSize) -> Self;
+}
+
+/// Width of a stored ID, independent of the width this build was compiled with.
+///
+/// Hashed IDs keep the low bytes of the same blake3 digest in both widths, so
+/// an `id64` ID is the `id128` ID of the same canonical string truncated to its
+/// low 64 bits. Runtime IDs are packed as `[session | counter]`:
+///
+/// | width   | session bits               | counter bits |
+/// |---------|----------------------------|--------------|
+/// | `id64`  | 24 (low bits of the seed)  | 40           |
+/// | `id128` | 64 (the full seed)         | 64           |
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IdWidth {
+    W64,
+    W128,
+}
+
+impl IdWidth {
+    #[cfg(feature = "id64")]
+    pub const CURRENT: Self = IdWidth::W64;
+    #[cfg(feature = "id128")]
+    pub const CURRENT: Self = IdWidth::W128;
+
+    pub const fn bits(self) -> u32 {
+        match self {
+            IdWidth::W64 => 64,
+            IdWidth::W128 => 128,
+        }
+    }
+
+    /// Largest raw ID value of this width.
+    pub const fn max_raw(self) -> u128 {
+        match self {
+            IdWidth::W64 => u64::MAX as u128,
+            IdWidth::W128 => u128::MAX,
+        }
+    }
+
+    pub const fn runtime_session_bits(self) -> u32 {
+        match self {
+            IdWidth::W64 => 24,
+            IdWidth::W128 => 64,
+        }
+    }
+
+    pub const fn runtime_counter_bits(self) -> u32 {
+        self.bits() - self.runtime_session_bits()
+    }
+
+    /// Packs a runtime ID; the seed is truncated to the session bits.
+    ///
+    /// Returns `None` if `counter` does not fit in the counter bits.
+    pub fn pack_runtime(self, session_seed: u64, counter: u64) -> Option<u128> {
+        let counter_bits = self.runtime_counter_bits();
+        if counter_bits < 64 && counter >> counter_bits != 0 {
+            return None;
+        }
+        let session = session_seed as u128 & low_mask(self.runtime_session_bits());
+        Some((session << counter_bits) | counter as u128)
+    }
+
+    /// Splits a runtime ID into its session bits and counter.
+    pub fn unpack_runtime(self, raw: u128) -> (u64, u64) {
+        let counter_bits = self.runtime_counter_bits();
+        let session = (raw >> counter_bits) & low_mask(self.runtime_session_bits());
+        let counter = raw & low_mask(counter_bits);
+        (session as u64, counter as u64)
+    }
+}
+
+const fn low_mask(bits: u32) -> u128 {
+    if bits >= 128 { u128::MAX } else { (1u128 << bits) - 1 }
+}
+
+/// Number of hex digits used when printing an ID of the configured width.
+pub const ID_HEX_WIDTH: usize = core::mem::size_of::<IdSize>() * 2;
+
+/// Hashes `prefix:canonical` into an ID hash of the configured width.
+pub fn hash_canonical(prefix: &str, canonical: &str) -> IdHash {
+    let full_str = format!("{prefix}:{canonical}");
+
+    #[cfg(feature = "id64")]
+    let hash = hash_str64(&full_str);
+
+    #[cfg(feature = "id128")]
+    let hash = hash_str128(&full_str);
+
+    hash
+}
+
+/// Parses the `prefix:hex` form produced by the `Display` impl of an ID.
+pub fn parse_prefixed_hex(prefix: &'static str, input: &str) -> Result<IdSize> {
+    let hex = input
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .ok_or_else(|| WMMSCoreError::InvalidValue(format!("expected '{prefix}:<hex>', got '{input}'")))?;
+
+    if hex.is_empty() || hex.len() > ID_HEX_WIDTH || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(WMMSCoreError::InvalidValue(format!("invalid {prefix} id hex: '{hex}'")));
+    }
+
+    IdSize::from_str_radix(hex, 16)
+        .map_err(|_| WMMSCoreError::InvalidValue(format!("invalid {prefix} id hex: '{hex}'")))
+}
+
+/// Declares a strongly-typed ID hashed from `prefix:canonical`.
+///
+/// The macro only refers to items through `$crate`, so downstream crates can
+/// declare their own ID kinds sharing the width and hashing rules of the
+/// built-in ones:
+///
+/// ```
+/// wmms_core::define_id!(QuestId, "quest");
+///
+/// let q = QuestId::new("main.prologue");
+/// let printed = q.to_string();
+/// assert!(printed.starts_with("quest:"));
+/// assert_eq!(printed.parse::<QuestId>().unwrap(), q);
+/// ```
 #[macro_export]
 macro_rules! define_id {
     ($name:ident, $prefix:expr) => {
@@ -30,32 +168,14 @@ macro_rules! define_id {
             const PREFIX: &'static str = $prefix;
         }
 
+        #[allow(clippy::unnecessary_cast)]
         impl $name {
             pub fn new(canonical: &str) -> Self {
-                $name(Self::from_canonical(Self::PREFIX, canonical))
-            }
-            
-            fn from_canonical(prefix: &'static str, canonical: &str) -> $crate::ids::IdHash {
-                let full_str = format!("{prefix}:{canonical}");
-
-                #[cfg(feature = "id64")]
-                let hash = hash_str64(&full_str);
-
-                #[cfg(feature = "id128")]
-                let hash = hash_str128(&full_str);
-
-                IdHash::from(hash)
+                $name($crate::ids::hash_canonical(<Self as $crate::ids::IdPrefix>::PREFIX, canonical))
             }
 
             pub fn as_idsize(&self) -> $crate::ids::IdSize {
-                #[cfg(feature = "id64")]
-                {
-                    self.0.as_u64() as $crate::ids::IdSize
-                }
-                #[cfg(feature = "id128")]
-                {
-                    self.0.as_u128() as $crate::ids::IdSize
-                }
+                self.0.raw()
             }
 
             pub fn as_u128(&self) -> u128 {
@@ -75,30 +195,126 @@ macro_rules! define_id {
             }
         }
 
-        impl From<u128> for  $name {
+        impl $crate::ids::CanonicalId for $name {
+            fn mint(canonical: &str) -> Self {
+                Self::new(canonical)
+            }
+
+            fn raw(&self) -> $crate::ids::IdSize {
+                self.as_idsize()
+            }
+
+            fn from_raw(raw: $crate::ids::IdSize) -> Self {
+                $name($crate::ids::IdHash::new(raw))
+            }
+        }
+
+        #[allow(clippy::unnecessary_cast)]
+        impl ::core::convert::From<u128> for $name {
             fn from(v: u128) -> Self {
-                $name($crate::ids::IdHash::new(v as IdSize))
+                $name($crate::ids::IdHash::new(v as $crate::ids::IdSize))
             }
-    
         }
 
-        impl From<u64> for  $name {
+        #[allow(clippy::unnecessary_cast)]
+        impl ::core::convert::From<u64> for $name {
             fn from(v: u64) -> Self {
-                $name($crate::ids::IdHash::new(v as IdSize))
+                $name($crate::ids::IdHash::new(v as $crate::ids::IdSize))
             }
-    
         }
 
-        impl Shr<u64> for $name {
+        impl ::core::ops::Shr<u64> for $name {
             type Output = u64;
 
             fn shr(self, rhs: u64) -> Self::Output {
-            (self >> rhs) as u64
+                self.as_idsize().checked_shr(rhs as u32).unwrap_or(0) as u64
+            }
+        }
+
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                write!(
+                    f,
+                    "{}:{:0width$x}",
+                    <Self as $crate::ids::IdPrefix>::PREFIX,
+                    self.as_idsize(),
+                    width = $crate::ids::ID_HEX_WIDTH
+                )
+            }
+        }
+
+        impl ::core::str::FromStr for $name {
+            type Err = $crate::error::WMMSCoreError;
+
+            fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                $crate::ids::parse_prefixed_hex(<Self as $crate::ids::IdPrefix>::PREFIX, s)
+                    .map(<Self as $crate::ids::CanonicalId>::from_raw)
+            }
+        }
+
+        #[allow(clippy::unnecessary_cast)]
+        impl $crate::hash::StableHash for $name {
+            fn stable_hash(&self, hasher: &mut $crate::hash::StableHasher) {
+                hasher.write_u128(self.as_idsize() as u128);
             }
         }
+
+        $crate::__define_id_serde!($name);
     };
 }
 
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use alloc::string::String as __String;
+
+/// Serde impls for `define_id!` types: `prefix:hex` strings in human-readable
+/// formats, the raw integer otherwise.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_serde {
+    ($name:ident) => {
+        impl $crate::ids::__serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::ids::__serde::Serializer,
+            {
+                if serializer.is_human_readable() {
+                    serializer.collect_str(self)
+                } else {
+                    $crate::ids::__serde::Serialize::serialize(&self.as_idsize(), serializer)
+                }
+            }
+        }
+
+        impl<'de> $crate::ids::__serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: $crate::ids::__serde::Deserializer<'de>,
+            {
+                if deserializer.is_human_readable() {
+                    let s: $crate::ids::__String = $crate::ids::__serde::Deserialize::deserialize(deserializer)?;
+                    s.parse().map_err(<D::Error as $crate::ids::__serde::de::Error>::custom)
+                } else {
+                    let raw: $crate::ids::IdSize = $crate::ids::__serde::Deserialize::deserialize(deserializer)?;
+                    Ok(<Self as $crate::ids::CanonicalId>::from_raw(raw))
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_serde {
+    ($name:ident) => {};
+}
+
 // WMMS Core ID types
 define_id!(AspectId, "aspect");
 define_id!(TraitId, "trait");
@@ -113,27 +329,37 @@ define_id!(EntityInstId,"entity_runtime"); // entity “runtime” ID
 define_id!(EffectInstId,"effect_instance"); // effect instance ID
 define_id!(EntityRid,"entity_rid"); // entity runtime ID
 
+/// Runtime entity IDs follow the layout documented on [`IdWidth`].
+#[allow(clippy::unnecessary_cast)]
 impl EntityInstId {
+    /// Packs `session_seed` and `counter`.
+    ///
+    /// Panics if `counter` overflows the counter bits (2^40 spawns per session
+    /// under `id64`); use [`Self::try_from_seed_counter`] to handle it.
     #[inline]
-      pub fn from_seed_counter(session_seed: u64, counter: u64) -> Self {
-        let v = ((session_seed as IdSize) << 64) | (counter as IdSize);
-        EntityInstId::from(v)
+    pub fn from_seed_counter(session_seed: u64, counter: u64) -> Self {
+        Self::try_from_seed_counter(session_seed, counter)
+            .expect("runtime entity counter exceeds the id width")
     }
 
     #[inline]
-    pub fn session_seed(&self) -> u64 {
-        (*self >> 64) as u64
+    pub fn try_from_seed_counter(session_seed: u64, counter: u64) -> Result<Self> {
+        IdWidth::CURRENT
+            .pack_runtime(session_seed, counter)
+            .map(EntityInstId::from)
+            .ok_or(WMMSCoreError::NumericOverflow)
     }
 
+    /// Session bits stored in the ID: the full seed under `id128`, its low 24
+    /// bits under `id64`.
     #[inline]
-    #[cfg(feature = "id64")]
-    pub fn counter(&self) -> u64 {
-        (self.0.as_u64() & 0xFFFFFFFFFFFFFFFF) as u64
+    pub fn session_seed(&self) -> u64 {
+        IdWidth::CURRENT.unpack_runtime(self.as_idsize() as u128).0
     }
+
     #[inline]
-    #[cfg(feature = "id128")]
-    pub fn counter(&self) -> u128 {
-        (self.0.as_u128() & 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF) as u128
+    pub fn counter(&self) -> u64 {
+        IdWidth::CURRENT.unpack_runtime(self.as_idsize() as u128).1
     }
 }
 
@@ -166,7 +392,7 @@ impl From<EntityInstId> for EntityId{
 }
 
 impl Display for EntityId{
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             EntityId::Auth(a) => write!(f, "Auth({:?})", a),
             #[cfg(feature = "id128")]
@@ -0,0 +1,141 @@
+use alloc::format;
+
+use crate::error::{Result, WMMSCoreError};
+use crate::hash::hash_str128;
+use crate::ids::{CanonicalId, IdSize, IdWidth};
+use crate::intern::IdInterner;
+
+/// How an ID kind is produced, which decides how it converts between widths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdScheme {
+    /// Hashed from `prefix:canonical`.
+    Canonical,
+    /// Packed `[session | counter]`, see [`IdWidth`].
+    Runtime,
+    /// Plain counter or index.
+    Sequential,
+}
+
+impl IdScheme {
+    /// Scheme of the built-in ID kinds; unknown prefixes are assumed canonical.
+    pub fn of_prefix(prefix: &str) -> Self {
+        match prefix {
+            "entity_runtime" => IdScheme::Runtime,
+            "entity_rid" | "effect_instance" => IdScheme::Sequential,
+            _ => IdScheme::Canonical,
+        }
+    }
+}
+
+/// Rewrites raw IDs stored at one width into the other.
+///
+/// Raw values travel as `u128` so a single build can read one width and write
+/// the other. Narrowing is lossless for canonical and sequential IDs that fit,
+/// and keeps the low 24 bits of runtime session seeds. Widening canonical IDs
+/// needs their names, since the high 64 bits of the hash are not stored.
+#[derive(Clone, Copy, Debug)]
+pub struct IdMigrator<'a> {
+    from: IdWidth,
+    to: IdWidth,
+    names: Option<&'a IdInterner>,
+    session_seed: Option<u64>,
+}
+
+impl<'a> IdMigrator<'a> {
+    pub fn new(from: IdWidth, to: IdWidth) -> Self {
+        Self { from, to, names: None, session_seed: None }
+    }
+
+    /// Names used to re-mint canonical IDs when widening.
+    ///
+    /// The interner must come from a build of the source width.
+    pub fn with_names(mut self, names: &'a IdInterner) -> Self {
+        self.names = Some(names);
+        self
+    }
+
+    /// Full session seed restored when widening runtime IDs whose stored
+    /// session bits match it; other runtime IDs keep their truncated bits.
+    pub fn with_session_seed(mut self, session_seed: u64) -> Self {
+        self.session_seed = Some(session_seed);
+        self
+    }
+
+    pub fn from_width(&self) -> IdWidth {
+        self.from
+    }
+
+    pub fn to_width(&self) -> IdWidth {
+        self.to
+    }
+
+    /// Migrates a raw ID, picking the scheme from its prefix.
+    pub fn migrate(&self, prefix: &str, raw: u128) -> Result<u128> {
+        self.migrate_as(prefix, IdScheme::of_prefix(prefix), raw)
+    }
+
+    /// Migrates an ID of the width this build was compiled with.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn migrate_id<I: CanonicalId>(&self, id: I) -> Result<u128> {
+        if self.from != IdWidth::CURRENT {
+            return Err(WMMSCoreError::InvalidValue(format!(
+                "migrator reads {}-bit ids, this build uses {}-bit ids",
+                self.from.bits(),
+                IdWidth::CURRENT.bits()
+            )));
+        }
+        self.migrate(I::PREFIX, id.raw() as u128)
+    }
+
+    pub fn migrate_as(&self, prefix: &str, scheme: IdScheme, raw: u128) -> Result<u128> {
+        if raw > self.from.max_raw() {
+            return Err(WMMSCoreError::InvalidValue(format!(
+                "{prefix} id {raw:x} does not fit in {} bits",
+                self.from.bits()
+            )));
+        }
+        if self.from == self.to {
+            return Ok(raw);
+        }
+
+        match scheme {
+            IdScheme::Canonical if self.to < self.from => Ok(raw & self.to.max_raw()),
+            IdScheme::Canonical => {
+                let name = self.canonical_name(prefix, raw).ok_or_else(|| {
+                    WMMSCoreError::InvalidValue(format!(
+                        "no canonical name recorded for {prefix}:{raw:x}, cannot widen it"
+                    ))
+                })?;
+                Ok(hash_str128(&format!("{prefix}:{name}")).raw() & self.to.max_raw())
+            }
+            IdScheme::Runtime => {
+                let (mut session, counter) = self.from.unpack_runtime(raw);
+                if let Some(seed) = self.session_seed {
+                    let stored = self.from.pack_runtime(seed, 0).map(|r| self.from.unpack_runtime(r).0);
+                    if stored == Some(session) {
+                        session = seed;
+                    }
+                }
+                self.to.pack_runtime(session, counter).ok_or_else(|| {
+                    WMMSCoreError::InvalidValue(format!(
+                        "{prefix} counter {counter} does not fit in {} bits",
+                        self.to.runtime_counter_bits()
+                    ))
+                })
+            }
+            IdScheme::Sequential if raw > self.to.max_raw() => Err(WMMSCoreError::InvalidValue(format!(
+                "{prefix} id {raw:x} does not fit in {} bits",
+                self.to.bits()
+            ))),
+            IdScheme::Sequential => Ok(raw),
+        }
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    fn canonical_name(&self, prefix: &str, raw: u128) -> Option<&'a str> {
+        if self.from != IdWidth::CURRENT {
+            return None;
+        }
+        self.names?.resolve_raw(prefix, raw as IdSize)
+    }
+}
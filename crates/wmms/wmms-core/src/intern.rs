@@ -0,0 +1,104 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use crate::canon::CanonMap;
+use crate::error::{Result, WMMSCoreError};
+use crate::ids::{CanonicalId, IdSize};
+
+/// Reverse-lookup table from minted IDs back to their canonical strings.
+///
+/// IDs are one-way hashes of `prefix:canonical`, so the interner records the
+/// canonical string every time an ID is minted through it. Two different
+/// canonical strings hashing to the same ID under the same prefix are reported
+/// as `WMMSCoreError::IdCollision`.
+#[derive(Clone, Debug, Default)]
+pub struct IdInterner {
+    names: CanonMap<&'static str, CanonMap<IdSize, String>>,
+}
+
+impl IdInterner {
+    pub fn new() -> Self {
+        Self { names: CanonMap::new() }
+    }
+
+    /// Mints the ID for `canonical` and records its name.
+    pub fn intern<I: CanonicalId>(&mut self, canonical: &str) -> Result<I> {
+        let id = I::mint(canonical);
+        self.record(id, canonical)?;
+        Ok(id)
+    }
+
+    /// Records the name of an already minted ID.
+    ///
+    /// Recording the same name twice is a no-op. On collision the table is left
+    /// untouched and the two names are reported in canonical order, so the
+    /// error does not depend on which one was seen first.
+    pub fn record<I: CanonicalId>(&mut self, id: I, canonical: &str) -> Result<()> {
+        self.record_raw(I::PREFIX, id.raw(), canonical)
+    }
+
+    /// Merges every name recorded in `other`, stopping at the first collision.
+    pub fn extend(&mut self, other: &IdInterner) -> Result<()> {
+        for (prefix, table) in &other.names {
+            for (raw, name) in table {
+                self.record_raw(prefix, *raw, name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record_raw(&mut self, prefix: &'static str, raw: IdSize, canonical: &str) -> Result<()> {
+        let table = self.names.entry(prefix).or_default();
+        match table.get(&raw) {
+            Some(existing) if existing == canonical => Ok(()),
+            Some(existing) => {
+                let (a, b) = if existing.as_str() <= canonical {
+                    (existing.clone(), canonical.to_string())
+                } else {
+                    (canonical.to_string(), existing.clone())
+                };
+                Err(WMMSCoreError::IdCollision { prefix, a, b })
+            }
+            None => {
+                table.insert(raw, canonical.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn resolve<I: CanonicalId>(&self, id: I) -> Option<&str> {
+        self.names.get(I::PREFIX)?.get(&id.raw()).map(String::as_str)
+    }
+
+    /// Looks a name up by ID prefix and raw value, for callers without a typed ID.
+    pub fn resolve_raw(&self, prefix: &str, raw: IdSize) -> Option<&str> {
+        self.names.get(prefix)?.get(&raw).map(String::as_str)
+    }
+
+    pub fn contains<I: CanonicalId>(&self, id: I) -> bool {
+        self.resolve(id).is_some()
+    }
+
+    /// Human-readable `prefix:canonical`, or `prefix:#<hex>` if the ID was never recorded.
+    pub fn display<I: CanonicalId>(&self, id: I) -> String {
+        match self.resolve(id) {
+            Some(name) => format!("{}:{}", I::PREFIX, name),
+            None => format!("{}:#{:x}", I::PREFIX, id.raw()),
+        }
+    }
+
+    /// Iterates the names recorded for one ID kind, in raw ID order.
+    pub fn names<I: CanonicalId>(&self) -> impl Iterator<Item = (I, &str)> + '_ {
+        self.names
+            .get(I::PREFIX)
+            .into_iter()
+            .flat_map(|t| t.iter().map(|(raw, name)| (I::from_raw(*raw), name.as_str())))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.values().map(|t| t.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.values().all(|t| t.is_empty())
+    }
+}
@@ -1,6 +1,19 @@
+//! Deterministic foundations shared by every wmms crate: IDs, hashing,
+//! canonical containers, ticks, fixed-point numbers and the seeded RNG.
+//!
+//! The crate is `no_std` + `alloc`. The default `std` feature only adds
+//! `miette` diagnostics and std support in the dependencies.
+#![no_std]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+extern crate self as wmms_core;
+
 pub mod error;
 pub mod hash;
 pub mod ids;
+pub mod intern;
 pub mod canon;
 pub mod time;
 pub mod num;
@@ -9,9 +22,330 @@ pub mod rng;
 pub mod prelude {
     pub use crate::error::{Result, WMMSCoreError};
     pub use crate::ids::*;
-    pub use crate::hash::{Hash128, Hash64};
+    pub use crate::intern::IdInterner;
+    pub use crate::hash::{Hash128, Hash64, StableHash, StableHasher};
     pub use crate::canon::{CanonicalKey, CanonMap, CanonSet, canon_sort};
     pub use crate::time::{Tick,TickDelta,TickRate};
-    pub use crate::num::{FixedU32, Q16_16, Q24_8, quantize_f32};
-    pub use crate::rng::DetRng;
+    pub use crate::time::calendar::{Calendar, CalendarBuilder, CalendarDate, LeapRule};
+    pub use crate::num::{Decimal, FixedI64, FixedU32, Q16_16, Q24_8, Q32_32, RoundingMode, quantize_f32};
+    pub use crate::rng::{DetRng, DetRngState};
+    pub use crate::rng::streams::{RngStreams, RngStreamsSnapshot};
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    use crate::prelude::*;
+
+    #[test]
+    fn interner_resolves_minted_ids() {
+        let mut names = IdInterner::new();
+        let fire: TraitId = names.intern("fire_affinity").unwrap();
+        let aspect: AspectId = names.intern("fire_affinity").unwrap();
+
+        assert_eq!(fire, TraitId::new("fire_affinity"));
+        assert_eq!(names.resolve(fire), Some("fire_affinity"));
+        assert_eq!(names.resolve(aspect), Some("fire_affinity"));
+        assert_eq!(names.resolve(TraitId::new("ice_affinity")), None);
+        assert_eq!(names.display(fire), "trait:fire_affinity");
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn interner_reports_collisions_in_canonical_order() {
+        let forced = TraitId::from(7u64);
+
+        let mut a = IdInterner::new();
+        a.record(forced, "zeta").unwrap();
+        a.record(forced, "zeta").unwrap();
+        let err_a = a.record(forced, "alpha").unwrap_err();
+
+        let mut b = IdInterner::new();
+        b.record(forced, "alpha").unwrap();
+        let err_b = b.record(forced, "zeta").unwrap_err();
+
+        for err in [err_a, err_b] {
+            match err {
+                WMMSCoreError::IdCollision { prefix, a, b } => {
+                    assert_eq!((prefix, a.as_str(), b.as_str()), ("trait", "alpha", "zeta"));
+                }
+                other => panic!("unexpected error: {other}"),
+            }
+        }
+        // Same raw value under another prefix is not a collision.
+        a.record(AspectId::from(7u64), "alpha").unwrap();
+    }
+
+    #[test]
+    fn ids_round_trip_through_display() {
+        let t = TraitId::new("pyromancer");
+        let printed = t.to_string();
+
+        assert_eq!(printed.len(), "trait:".len() + ID_HEX_WIDTH);
+        assert_eq!(printed.parse::<TraitId>().unwrap(), t);
+        assert!(printed.parse::<AspectId>().is_err());
+        assert!("trait:xyz".parse::<TraitId>().is_err());
+    }
+
+    #[test]
+    fn fixed_arithmetic_checks_and_round_trips() {
+        let a: Q16_16 = "2.5".parse().unwrap();
+        let b = Q16_16::from_i32(4);
+
+        assert_eq!(a * b, Q16_16::from_i32(10));
+        assert_eq!((b / a).raw(), 104_857); // truncated, not rounded
+        assert_eq!(Q16_16::MAX.checked_add(a), None);
+        assert_eq!(Q16_16::MAX + a, Q16_16::MAX);
+        assert!(a.try_div(Q16_16::ZERO).is_err());
+        assert_eq!(Q16_16::lerp(Q16_16::ZERO, b, Q16_16::from_raw(1 << 15)), Q16_16::from_i32(2));
+        assert_eq!(a.convert::<8>().unwrap(), Q24_8::from_raw(640));
+        assert!(Q24_8::from_i32(40_000).convert::<16>().is_err());
+
+        for raw in [-98_304, -1, 0, 1, 6_554, 205_887, i32::MAX, i32::MIN] {
+            let v = Q16_16::from_raw(raw);
+            assert_eq!(v.to_string().parse::<Q16_16>().unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn fixed_math_is_accurate_and_bit_stable() {
+        let close = |v: Q16_16, expected: f64| (v.to_f32() as f64 - expected).abs() < 4e-5;
+
+        assert_eq!(Q16_16::from_i32(9).sqrt().unwrap(), Q16_16::from_i32(3));
+        assert!(Q16_16::from_i32(-1).sqrt().is_err());
+        assert!(close(Q16_16::ONE.exp().unwrap(), core::f64::consts::E));
+        assert!(close(Q16_16::from_i32(10).ln().unwrap(), 10f64.ln()));
+        assert!(Q16_16::ZERO.ln().is_err());
+        assert!(Q16_16::from_i32(20).exp().is_err());
+        assert_eq!(Q16_16::from_i32(2).powi(10).unwrap(), Q16_16::from_i32(1024));
+        assert!(close(Q16_16::from_i32(2).pow("0.5".parse().unwrap()).unwrap(), 2f64.sqrt()));
+
+        assert_eq!(Q16_16::ZERO.sin(), Q16_16::ZERO);
+        assert_eq!(Q16_16::HALF_PI.sin(), Q16_16::ONE);
+        assert!(close(Q16_16::from_i32(1).sin(), 1f64.sin()));
+        assert!(close(Q16_16::from_i32(-4).cos(), (-4f64).cos()));
+        assert!(close(Q16_16::atan2(Q16_16::ONE, -Q16_16::ONE), 3.0 * core::f64::consts::FRAC_PI_4));
+
+        // Pinned raw bits: any change here breaks replays.
+        assert_eq!(Q16_16::from_i32(3).sin().raw(), 9_248);
+        assert_eq!(Q16_16::from_i32(2).ln().unwrap().raw(), 45_426);
+    }
+
+    #[test]
+    fn wide_fixed_reports_overflow() {
+        let pop = Q32_32::from_i64(800_000_000).unwrap();
+        let growth: Q32_32 = "1.015".parse().unwrap();
+
+        assert_eq!(pop.try_mul(Q32_32::from_i64(2).unwrap()).unwrap().to_i64_floor(), 1_600_000_000);
+        assert_eq!(pop.try_mul(growth).unwrap().to_i64_round(), 812_000_000);
+        assert!(matches!(pop.try_mul(pop), Err(WMMSCoreError::NumericOverflow)));
+        assert!(Q32_32::from_i64(3_000_000_000).is_err());
+        assert!(matches!(Q32_32::MAX.try_add(Q32_32::EPSILON), Err(WMMSCoreError::NumericOverflow)));
+        assert!(pop.to_fixed32::<16>().is_err());
+        assert_eq!(Q32_32::from_fixed32(Q16_16::from_i32(-3)).unwrap(), Q32_32::from_i64(-3).unwrap());
+        assert_eq!(Q32_32::from_i64(1_000_000).unwrap().sqrt().unwrap(), Q32_32::from_i64(1_000).unwrap());
+        assert_eq!(growth.to_string().parse::<Q32_32>().unwrap(), growth);
+    }
+
+    #[test]
+    fn decimal_is_exact_and_rounds_explicitly() {
+        let price: Decimal = "19.99".parse().unwrap();
+        let qty = Decimal::from_i64(3);
+        let total = price.try_mul(qty).unwrap();
+
+        assert_eq!(total.to_string(), "59.97");
+        assert_eq!("12.50".parse::<Decimal>().unwrap(), "12.5".parse::<Decimal>().unwrap());
+        assert_eq!("12.50".parse::<Decimal>().unwrap().to_string(), "12.50");
+        assert_eq!("-0.05".parse::<Decimal>().unwrap().to_string(), "-0.05");
+
+        let third = Decimal::ONE.try_div(Decimal::from_i64(3), 2, RoundingMode::HalfEven).unwrap();
+        assert_eq!(third.to_string(), "0.33");
+        let half: Decimal = "2.5".parse().unwrap();
+        assert_eq!(half.round_dp(0, RoundingMode::HalfEven), Decimal::from_i64(2));
+        assert_eq!(half.round_dp(0, RoundingMode::HalfUp), Decimal::from_i64(3));
+
+        let huge = Decimal::new(i128::MAX, 0).unwrap();
+        assert!(matches!(huge.try_add(Decimal::ONE), Err(WMMSCoreError::NumericOverflow)));
+        assert_eq!(price.to_fixed64::<32>(RoundingMode::HalfEven).unwrap().to_i64_floor(), 19);
+    }
+
+    #[test]
+    fn rng_distributions_are_pinned() {
+        // Pinned outputs: a change here means saved sessions no longer replay.
+        let mut r = DetRng::from_seed_u64(42);
+        let v: Vec<u64> = (0..5).map(|_| r.below(100)).collect();
+        assert_eq!(v, [43, 16, 67, 68, 6]);
+
+        let mut r = DetRng::from_seed_u64(42);
+        let roll = r.roll_dice("4d6kh3+2").unwrap();
+        assert_eq!(roll.total, 15);
+        assert_eq!(roll.dice.iter().filter(|d| !d.kept).map(|d| d.value).collect::<Vec<_>>(), [1]);
+
+        let mut items = [1, 2, 3, 4, 5, 6, 7, 8];
+        r.shuffle(&mut items);
+        assert_eq!(items, [4, 6, 5, 3, 8, 7, 2, 1]);
+        assert_eq!(r.weighted_index(&[0, 10, 30, 60]), Some(2));
+        assert_eq!(r.sample_reservoir(0..100, 4), [18, 27, 69, 72]);
+
+        assert_eq!(r.weighted_index(&[0, 0]), None);
+        for _ in 0..100 {
+            assert!((-3..=3).contains(&r.range_i64(-3..=3)));
+            let f = r.range_fixed(Q16_16::from_i32(1), Q16_16::from_i32(2));
+            assert!(f >= Q16_16::from_i32(1) && f < Q16_16::from_i32(2));
+        }
+    }
+
+    #[test]
+    fn dice_expressions_parse_and_bound() {
+        use crate::rng::dice::DiceExpr;
+
+        let e: DiceExpr = " 2d20KL1 - d4 + 3 ".parse().unwrap();
+        assert_eq!(e.to_string(), "2d20kl1-1d4+3");
+        assert_eq!((e.min(), e.max()), (0, 22));
+        assert_eq!("d%".parse::<DiceExpr>().unwrap().max(), 100);
+
+        for bad in ["", "3d", "0d6", "2d6kx1", "1d6+", "d0"] {
+            assert!(bad.parse::<DiceExpr>().is_err(), "{bad:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn rng_streams_resume_from_snapshot() {
+        use rand::RngCore;
+
+        let mut streams = RngStreams::new(7);
+        streams.stream("combat").next_u32();
+        streams.stream("loot").below(10);
+        streams.entity_stream(EntityInstId::from_seed_counter(7, 1)).next_u64();
+
+        // Creation order does not affect derived streams.
+        let mut other = RngStreams::new(7);
+        assert_eq!(other.stream("loot").below(10), DetRng::derive(7, "loot").below(10));
+
+        let snap = RngStreamsSnapshot::from_bytes(&streams.snapshot().to_bytes()).unwrap();
+        assert_eq!(snap, streams.snapshot());
+        let mut resumed = RngStreams::restore(&snap);
+        for tag in ["combat", "loot", "weather"] {
+            let expected: Vec<u64> = (0..3).map(|_| streams.stream(tag).next_u64()).collect();
+            let got: Vec<u64> = (0..3).map(|_| resumed.stream(tag).next_u64()).collect();
+            assert_eq!(got, expected, "stream {tag} diverged");
+        }
+
+        // An odd word position (after next_u32) restores exactly.
+        let mut rng = DetRng::from_seed_u64(3);
+        rng.next_u32();
+        let mut copy = DetRng::from_state(&DetRngState::from_bytes(&rng.state().to_bytes()).unwrap());
+        assert_eq!(copy.next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn calendar_maps_ticks_to_dates_and_back() {
+        let day = 24;
+        let cal = CalendarBuilder::new("reckoning", day)
+            .epoch(1, 0)
+            .month("Frostmoon", 30)
+            .leap_month("Thawmoon", 29, 1)
+            .month("Suncrest", 31)
+            .weekdays(&["Moonday", "Starday", "Ashday", "Restday", "Feastday"])
+            .leap_rule(LeapRule::GREGORIAN)
+            .moon("Selune", TickDelta(28 * day), TickDelta(0))
+            .era("First Age", 1)
+            .era("Second Age", 1000)
+            .build()
+            .unwrap();
+
+        assert_eq!(cal.days_in_year(4), 91);
+        assert_eq!(cal.days_in_year(100), 90);
+        assert_eq!(cal.days_in_year(400), 91);
+
+        let pattern = "{day_ord} of {month}, Year {era_year} of the {era}";
+        let tick = cal.parse("3rd of Frostmoon, Year 412 of the Second Age", pattern).unwrap();
+        let date = cal.date_of(tick);
+        assert_eq!((date.year, date.month, date.day), (1411, 0, 3));
+        assert_eq!(cal.format(tick, pattern).unwrap(), "3rd of Frostmoon, Year 412 of the Second Age");
+
+        for t in [0, 1, day * 89, day * 90, day * 91, day * 36_400 + 5, day * 1_000_003 + 23] {
+            let d = cal.date_of(Tick(t));
+            assert_eq!(cal.tick_of(&d).unwrap(), Tick(t), "{d:?}");
+        }
+
+        assert_eq!(cal.weekday_of(Tick(day * 6)), Some("Starday"));
+        assert_eq!(cal.moon_phase(0, Tick(14 * day)).unwrap().name, "full");
+        assert!(cal.parse("30th of Thawmoon, Year 2 of the First Age", pattern).is_err());
+        assert!(cal.parse("30th of Thawmoon, Year 4 of the First Age", pattern).is_ok());
+        assert!(cal.parse("1st of Nomoon, Year 4 of the First Age", pattern).is_err());
+    }
+
+    #[derive(StableHash)]
+    struct Sample {
+        id: TraitId,
+        tags: CanonMap<String, Q16_16>,
+        #[stable_hash(skip)]
+        _cache: Option<u64>,
+    }
+
+    #[derive(StableHash)]
+    enum Shape {
+        Unit,
+        Pair(u32, u32),
+    }
+
+    #[test]
+    fn stable_hash_is_structural() {
+        let mut a = Sample { id: TraitId::new("fire"), tags: CanonMap::new(), _cache: None };
+        a.tags.insert("b".into(), Q16_16::from_i32(2));
+        a.tags.insert("a".into(), Q16_16::from_i32(1));
+        let mut b = Sample { id: TraitId::new("fire"), tags: CanonMap::new(), _cache: Some(9) };
+        b.tags.insert("a".into(), Q16_16::from_i32(1));
+        b.tags.insert("b".into(), Q16_16::from_i32(2));
+        assert_eq!(a.stable_digest(), b.stable_digest());
+
+        b.tags.insert("c".into(), Q16_16::ZERO);
+        assert_ne!(a.stable_digest(), b.stable_digest());
+
+        assert_ne!(("ab", "c").stable_digest(), ("a", "bc").stable_digest());
+        assert_ne!(Shape::Unit.stable_digest(), Shape::Pair(0, 0).stable_digest());
+        assert_eq!((-0.0f32).stable_digest(), 0.0f32.stable_digest());
+        assert_eq!(
+            "1.50".parse::<Decimal>().unwrap().stable_digest(),
+            "1.5".parse::<Decimal>().unwrap().stable_digest()
+        );
+    }
+
+    #[test]
+    fn id_width_migration() {
+        use crate::hash::{hash_str128, hash_str64};
+        use crate::ids::migrate::{IdMigrator, IdScheme};
+
+        let w64 = IdWidth::W64;
+        let raw = w64.pack_runtime(0xABCD_EF12_3456, 99).unwrap();
+        assert_eq!(w64.unpack_runtime(raw), (0x12_3456, 99));
+        assert!(w64.pack_runtime(1, 1 << 40).is_none());
+        let id = EntityInstId::from_seed_counter(0x12_3456, 99);
+        assert_eq!((id.session_seed(), id.counter()), IdWidth::CURRENT.unpack_runtime(id.as_u128()));
+
+        // id64 hashes are the low half of the id128 hash of the same string.
+        assert_eq!(hash_str128("trait:fire").raw() as u64, hash_str64("trait:fire").raw());
+
+        let down = IdMigrator::new(IdWidth::W128, IdWidth::W64);
+        let wide_fire = hash_str128("trait:fire").raw();
+        assert_eq!(down.migrate("trait", wide_fire).unwrap(), hash_str64("trait:fire").raw() as u128);
+        assert!(down.migrate("entity_rid", 1 << 70).is_err());
+        let wide_rt = IdWidth::W128.pack_runtime(0xABCD_EF12_3456, 99).unwrap();
+        assert_eq!(down.migrate("entity_runtime", wide_rt).unwrap(), raw);
+
+        let up = IdMigrator::new(IdWidth::W64, IdWidth::W128).with_session_seed(0xABCD_EF12_3456);
+        assert_eq!(up.migrate_as("entity_runtime", IdScheme::Runtime, raw).unwrap(), wide_rt);
+        assert_eq!(up.migrate("effect_instance", 7).unwrap(), 7);
+        assert!(up.migrate("trait", hash_str64("trait:fire").raw() as u128).is_err());
+
+        if IdWidth::CURRENT == IdWidth::W64 {
+            let mut names = IdInterner::new();
+            let fire: TraitId = names.intern("fire").unwrap();
+            let up = up.with_names(&names);
+            assert_eq!(up.migrate_id(fire).unwrap(), wide_fire);
+        }
+    }
 }
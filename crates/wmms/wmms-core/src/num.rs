@@ -1,5 +1,18 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use core::str::FromStr;
+
 use crate::error::{Result, WMMSCoreError};
 
+pub mod math;
+mod wide;
+mod decimal;
+
+pub use decimal::{Decimal, RoundingMode};
+pub use wide::{FixedI64, Q32_32};
+
 #[repr(transparent)]
 #[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
 pub struct FixedU32<const FRAC_BITS: u32>(pub i32);
@@ -9,12 +22,48 @@ pub type Q24_8 = FixedU32<8>;
 
 impl<const FRAC: u32> FixedU32<FRAC> {
     pub const SCALE:i64 = 1i64 << FRAC;
+    pub const FRAC_MASK: i32 = (Self::SCALE - 1) as i32;
+
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(Self::SCALE as i32);
+    pub const MIN: Self = Self(i32::MIN);
+    pub const MAX: Self = Self(i32::MAX);
+    /// Smallest positive representable value.
+    pub const EPSILON: Self = Self(1);
+
+    #[inline]
+    pub const fn from_raw(raw: i32) -> Self {
+        Self(raw)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> i32 {
+        self.0
+    }
 
     #[inline]
     pub fn from_i32(v: i32)->Self{
         Self(v.saturating_mul(Self::SCALE as i32))
     }
 
+    pub fn checked_from_i32(v: i32) -> Option<Self> {
+        v.checked_mul(Self::SCALE as i32).map(Self)
+    }
+
+    /// Integer part, rounded toward negative infinity.
+    #[inline]
+    pub fn to_i32_floor(self) -> i32 {
+        self.0 >> FRAC
+    }
+
+    /// Integer part, rounded to nearest with ties away from zero.
+    pub fn to_i32_round(self) -> i32 {
+        let half = Self::SCALE >> 1;
+        let v = self.0 as i64;
+        let r = if v >= 0 { (v + half) >> FRAC } else { -((-v + half) >> FRAC) };
+        r as i32
+    }
+
     #[inline]
     pub fn to_f32(self)->f32{
         (self.0 as f32) / (Self::SCALE as f32)
@@ -24,52 +73,341 @@ impl<const FRAC: u32> FixedU32<FRAC> {
     // Rounds to nearest representable value
     pub fn from_f32_quantized(v: f32) -> Self{
         let scaled = (v as f64) * (Self::SCALE as f64);
-        let raw = if scaled >= 0.0 {
-            (scaled + 0.5).floor() as i32
-        } else {
-            (scaled - 0.5).ceil() as i32
-        };
+        Self(round_half_away(scaled) as i32)
+    }
+
+    /// Builds a value from a raw i64 that may be out of range.
+    #[inline]
+    fn from_wide(raw: i64) -> Option<Self> {
+        i32::try_from(raw).ok().map(Self)
+    }
+
+    #[inline]
+    fn saturate_wide(raw: i64) -> Self {
+        Self(raw.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+
+    // ----- checked arithmetic -----
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        Self::from_wide((self.0 as i64 * other.0 as i64) >> FRAC)
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.0 == 0 {
+            return None;
+        }
+        Self::from_wide(((self.0 as i64) << FRAC) / other.0 as i64)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Self)
+    }
 
-        Self(raw as i32)
+    pub fn checked_abs(self) -> Option<Self> {
+        self.0.checked_abs().map(Self)
     }
 
-    pub fn add(self, other:Self) -> Self{
+    // ----- saturating arithmetic -----
+
+    pub fn saturating_add(self, other: Self) -> Self {
         Self(self.0.saturating_add(other.0))
     }
-    pub fn sub(self, other:Self) -> Self{
+
+    pub fn saturating_sub(self, other: Self) -> Self {
         Self(self.0.saturating_sub(other.0))
     }
 
-    pub fn mul(self, other:Self) -> Self{
-        let a = self.0 as i64;
-        let b = other.0 as i64;
-        let raw = (a *b) >> FRAC;
-        Self(raw as i32)
+    pub fn saturating_mul(self, other: Self) -> Self {
+        Self::saturate_wide((self.0 as i64 * other.0 as i64) >> FRAC)
+    }
+
+    /// Saturating division. Panics if `other` is zero, like the integer version.
+    pub fn saturating_div(self, other: Self) -> Self {
+        assert!(other.0 != 0, "attempt to divide by zero");
+        Self::saturate_wide(((self.0 as i64) << FRAC) / other.0 as i64)
+    }
+
+    pub fn saturating_neg(self) -> Self {
+        Self(self.0.saturating_neg())
+    }
+
+    pub fn saturating_abs(self) -> Self {
+        Self(self.0.saturating_abs())
     }
 
-    pub fn div (self, other: Self) -> Result<Self> {
+    /// Division reporting errors instead of panicking or saturating.
+    pub fn try_div(self, other: Self) -> Result<Self> {
         if other.0 == 0 {
             return Err(WMMSCoreError::InvalidValue("Division by zero".into()));
         }
-        let a =(self.0 as i64) << FRAC;
-        let b = other.0 as i64;
-        let raw = a / b;
-        Ok(Self(raw.clamp(i32::MIN as i64, i32::MAX as i64) as i32))
+        self.checked_div(other).ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    // ----- helpers -----
+
+    #[inline]
+    pub fn abs(self) -> Self {
+        self.saturating_abs()
+    }
+
+    #[inline]
+    pub fn signum(self) -> i32 {
+        self.0.signum()
+    }
+
+    #[inline]
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn floor(self) -> Self {
+        Self(self.0 & !Self::FRAC_MASK)
+    }
+
+    pub fn ceil(self) -> Self {
+        if self.0 & Self::FRAC_MASK == 0 {
+            self
+        } else {
+            Self::saturate_wide((self.0 & !Self::FRAC_MASK) as i64 + Self::SCALE)
+        }
+    }
+
+    /// Rounds to the nearest integer, ties away from zero.
+    pub fn round(self) -> Self {
+        Self::saturate_wide(self.to_i32_round() as i64 * Self::SCALE)
+    }
+
+    /// Fractional part, always in `[0, 1)`.
+    pub fn fract(self) -> Self {
+        Self(self.0 & Self::FRAC_MASK)
+    }
+
+    pub fn clamp(self, min: Self, max: Self) -> Self {
+        Ord::clamp(self, min, max)
+    }
+
+    /// Linear interpolation `a + (b - a) * t`, saturating on overflow.
+    pub fn lerp(a: Self, b: Self, t: Self) -> Self {
+        let delta = b.0 as i64 - a.0 as i64;
+        let step = (delta * t.0 as i64) >> FRAC;
+        Self::saturate_wide(a.0 as i64 + step)
+    }
+
+    // ----- Q format conversions -----
+
+    /// Converts to another fractional width, rounding to nearest when bits are dropped.
+    pub fn convert<const TO: u32>(self) -> Result<FixedU32<TO>> {
+        let raw = Self::rescale(self.0 as i64, FRAC, TO);
+        FixedU32::<TO>::from_wide(raw).ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn saturating_convert<const TO: u32>(self) -> FixedU32<TO> {
+        FixedU32::<TO>::saturate_wide(Self::rescale(self.0 as i64, FRAC, TO))
+    }
+
+    fn rescale(raw: i64, from: u32, to: u32) -> i64 {
+        if to >= from {
+            raw << (to - from)
+        } else {
+            let shift = from - to;
+            let half = 1i64 << (shift - 1);
+            if raw >= 0 { (raw + half) >> shift } else { -((-raw + half) >> shift) }
+        }
+    }
+}
+
+impl<const FRAC: u32> Add for FixedU32<FRAC> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        self.saturating_add(other)
+    }
+}
+
+impl<const FRAC: u32> Sub for FixedU32<FRAC> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        self.saturating_sub(other)
+    }
+}
+
+impl<const FRAC: u32> Mul for FixedU32<FRAC> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        self.saturating_mul(other)
+    }
+}
+
+impl<const FRAC: u32> Div for FixedU32<FRAC> {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, other: Self) -> Self {
+        self.saturating_div(other)
+    }
+}
+
+impl<const FRAC: u32> Neg for FixedU32<FRAC> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        self.saturating_neg()
+    }
+}
+
+impl<const FRAC: u32> AddAssign for FixedU32<FRAC> {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl<const FRAC: u32> SubAssign for FixedU32<FRAC> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl<const FRAC: u32> MulAssign for FixedU32<FRAC> {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
+
+impl<const FRAC: u32> DivAssign for FixedU32<FRAC> {
+    fn div_assign(&mut self, other: Self) {
+        *self = *self / other;
+    }
+}
+
+// Decimal text form. Without an explicit precision the shortest string that
+// parses back to the same raw value is printed, so Display/FromStr round-trip.
+impl<const FRAC: u32> fmt::Display for FixedU32<FRAC> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match f.precision() {
+            Some(p) => format_fixed(self.0 as i128, FRAC, p as u32),
+            None => format_fixed_shortest(self.0 as i128, FRAC),
+        };
+        pad_signed(f, &s)
+    }
+}
+
+impl<const FRAC: u32> FromStr for FixedU32<FRAC> {
+    type Err = WMMSCoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw = parse_fixed(s, FRAC)?;
+        i32::try_from(raw).map(Self).map_err(|_| WMMSCoreError::NumericOverflow)
+    }
+}
+
+/// Pads a pre-formatted signed number, honouring width, fill and `+` flags.
+pub(crate) fn pad_signed(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    match s.strip_prefix('-') {
+        Some(body) => f.pad_integral(false, "", body),
+        None => f.pad_integral(true, "", s),
+    }
+}
+
+/// Max fractional digits considered when parsing; later digits are ignored.
+const MAX_PARSE_DIGITS: usize = 18;
+
+/// Max fractional digits produced when formatting; enough to round-trip any width up to 63 bits.
+const MAX_FORMAT_DIGITS: u32 = 19;
+
+/// Formats a raw fixed-point value with `digits` decimals, rounding half away from zero.
+pub(crate) fn format_fixed(raw: i128, frac_bits: u32, digits: u32) -> String {
+    let digits = digits.min(MAX_FORMAT_DIGITS);
+    let neg = raw < 0;
+    let mag = raw.unsigned_abs();
+    let scale = 10u128.pow(digits);
+    let mask = (1u128 << frac_bits) - 1;
+    let mut int_part = mag >> frac_bits;
+    let mut frac_part = ((mag & mask) * scale + ((1u128 << frac_bits) >> 1)) >> frac_bits;
+    if frac_part == scale {
+        int_part += 1;
+        frac_part = 0;
+    }
+
+    let sign = if neg && (int_part != 0 || frac_part != 0) { "-" } else { "" };
+    if digits == 0 {
+        format!("{sign}{int_part}")
+    } else {
+        format!("{sign}{int_part}.{frac_part:0width$}", width = digits as usize)
     }
 }
 
+/// Shortest decimal form of a raw fixed-point value that parses back to the same bits.
+pub(crate) fn format_fixed_shortest(raw: i128, frac_bits: u32) -> String {
+    (0..=frac_bits.min(MAX_FORMAT_DIGITS))
+        .map(|digits| format_fixed(raw, frac_bits, digits))
+        .find(|s| parse_fixed(s, frac_bits).ok() == Some(raw))
+        .unwrap_or_else(|| format_fixed(raw, frac_bits, MAX_FORMAT_DIGITS))
+}
+
+/// Parses `[-]int[.frac]` into a raw fixed-point value, rounding to nearest.
+pub(crate) fn parse_fixed(input: &str, frac_bits: u32) -> Result<i128> {
+    let invalid = || WMMSCoreError::InvalidValue(format!("invalid fixed-point number: '{input}'"));
+    let s = input.trim();
+    let (neg, body) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (int_str, frac_str) = body.split_once('.').unwrap_or((body, ""));
+    if int_str.is_empty() && frac_str.is_empty() {
+        return Err(invalid());
+    }
+    if !int_str.bytes().chain(frac_str.bytes()).all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let int_part: u128 = if int_str.is_empty() {
+        0
+    } else {
+        int_str.parse().map_err(|_| WMMSCoreError::NumericOverflow)?
+    };
+    let frac_str = &frac_str[..frac_str.len().min(MAX_PARSE_DIGITS)];
+    let frac_num: u128 = if frac_str.is_empty() { 0 } else { frac_str.parse().map_err(|_| invalid())? };
+    let frac_den = 10u128.pow(frac_str.len() as u32);
+    let frac_raw = ((frac_num << frac_bits) + frac_den / 2) / frac_den;
+
+    let mag = int_part
+        .checked_mul(1u128 << frac_bits)
+        .and_then(|v| v.checked_add(frac_raw))
+        .and_then(|v| i128::try_from(v).ok())
+        .ok_or(WMMSCoreError::NumericOverflow)?;
+    Ok(if neg { -mag } else { mag })
+}
+
 // Quantization helper
 pub fn quantize_f32(v: f32, step: f32) -> f32 {
     if step == 0.0 {
         return v;
     }
     let q = (v / step) as f64;
-    let r = if q >= 0.0 {
-        (q + 0.5).floor()
-    } else {
-        (q - 0.5).ceil()
-    };
-
-    (r as f32) * step
+    (round_half_away(q) as f32) * step
 }
 
+// Rounds half away from zero without `f64::floor`, which core does not provide.
+// Above 2^52 every f64 is already an integer; below it the i64 cast is exact.
+pub(crate) fn round_half_away(v: f64) -> f64 {
+    if !v.is_finite() || v.abs() >= (1u64 << 52) as f64 {
+        return v;
+    }
+    let shifted = if v >= 0.0 { v + 0.5 } else { v - 0.5 };
+    shifted as i64 as f64
+}
@@ -0,0 +1,296 @@
+use alloc::format;
+use alloc::string::ToString;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
+use crate::error::{Result, WMMSCoreError};
+
+use super::FixedI64;
+
+/// Rounding rule applied whenever a decimal loses digits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RoundingMode {
+    /// Round half to even ("banker's rounding").
+    #[default]
+    HalfEven,
+    /// Round half away from zero.
+    HalfUp,
+    /// Truncate toward zero.
+    Down,
+    /// Toward negative infinity.
+    Floor,
+    /// Toward positive infinity.
+    Ceiling,
+}
+
+/// Deterministic base-10 number `mantissa * 10^-scale`, meant for currency.
+///
+/// Values keep the scale they were written with (`12.50` stays `12.50`), but
+/// equality, ordering and hashing are numeric: `12.5 == 12.50`.
+#[derive(Clone, Copy, Debug)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub const MAX_SCALE: u32 = 28;
+
+    pub const ZERO: Self = Self { mantissa: 0, scale: 0 };
+    pub const ONE: Self = Self { mantissa: 1, scale: 0 };
+
+    pub fn new(mantissa: i128, scale: u32) -> Result<Self> {
+        if scale > Self::MAX_SCALE {
+            return Err(WMMSCoreError::InvalidValue(format!("decimal scale {scale} exceeds {}", Self::MAX_SCALE)));
+        }
+        Ok(Self { mantissa, scale })
+    }
+
+    pub fn from_i64(v: i64) -> Self {
+        Self { mantissa: v as i128, scale: 0 }
+    }
+
+    #[inline]
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    #[inline]
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    #[inline]
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    pub fn try_neg(self) -> Result<Self> {
+        let mantissa = self.mantissa.checked_neg().ok_or(WMMSCoreError::NumericOverflow)?;
+        Ok(Self { mantissa, ..self })
+    }
+
+    pub fn try_abs(self) -> Result<Self> {
+        let mantissa = self.mantissa.checked_abs().ok_or(WMMSCoreError::NumericOverflow)?;
+        Ok(Self { mantissa, ..self })
+    }
+
+    /// Changes the scale, rounding with `mode` when digits are dropped.
+    pub fn rescale(self, scale: u32, mode: RoundingMode) -> Result<Self> {
+        if scale > Self::MAX_SCALE {
+            return Err(WMMSCoreError::InvalidValue(format!("decimal scale {scale} exceeds {}", Self::MAX_SCALE)));
+        }
+        let mantissa = if scale >= self.scale {
+            self.mantissa
+                .checked_mul(pow10(scale - self.scale)?)
+                .ok_or(WMMSCoreError::NumericOverflow)?
+        } else {
+            div_round(self.mantissa, pow10(self.scale - scale)?, mode)
+        };
+        Ok(Self { mantissa, scale })
+    }
+
+    /// Rounds to at most `dp` decimal places. Never overflows.
+    pub fn round_dp(self, dp: u32, mode: RoundingMode) -> Self {
+        if dp >= self.scale {
+            return self;
+        }
+        Self { mantissa: div_round(self.mantissa, 10i128.pow(self.scale - dp), mode), scale: dp }
+    }
+
+    /// Strips trailing zeros from the fractional part.
+    pub fn normalize(self) -> Self {
+        let mut n = self;
+        while n.scale > 0 && n.mantissa % 10 == 0 {
+            n.mantissa /= 10;
+            n.scale -= 1;
+        }
+        n
+    }
+
+    fn aligned(self, other: Self) -> Result<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescale(scale, RoundingMode::Down)?.mantissa;
+        let b = other.rescale(scale, RoundingMode::Down)?.mantissa;
+        Ok((a, b, scale))
+    }
+
+    pub fn try_add(self, other: Self) -> Result<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        let mantissa = a.checked_add(b).ok_or(WMMSCoreError::NumericOverflow)?;
+        Ok(Self { mantissa, scale })
+    }
+
+    pub fn try_sub(self, other: Self) -> Result<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        let mantissa = a.checked_sub(b).ok_or(WMMSCoreError::NumericOverflow)?;
+        Ok(Self { mantissa, scale })
+    }
+
+    /// Exact product; rounded half-even only if the scale would exceed `MAX_SCALE`.
+    pub fn try_mul(self, other: Self) -> Result<Self> {
+        let mantissa = self.mantissa.checked_mul(other.mantissa).ok_or(WMMSCoreError::NumericOverflow)?;
+        let scale = self.scale + other.scale;
+        if scale > Self::MAX_SCALE {
+            let mantissa = div_round(mantissa, pow10(scale - Self::MAX_SCALE)?, RoundingMode::HalfEven);
+            return Ok(Self { mantissa, scale: Self::MAX_SCALE });
+        }
+        Ok(Self { mantissa, scale })
+    }
+
+    /// Quotient rounded to `scale` decimal places with `mode`.
+    pub fn try_div(self, other: Self, scale: u32, mode: RoundingMode) -> Result<Self> {
+        if other.mantissa == 0 {
+            return Err(WMMSCoreError::InvalidValue("Division by zero".into()));
+        }
+        if scale > Self::MAX_SCALE {
+            return Err(WMMSCoreError::InvalidValue(format!("decimal scale {scale} exceeds {}", Self::MAX_SCALE)));
+        }
+        // self.m / 10^sa / (other.m / 10^sb) * 10^scale
+        let exp = scale as i64 + other.scale as i64 - self.scale as i64;
+        let (num, den) = if exp >= 0 {
+            let num = self.mantissa.checked_mul(pow10(exp as u32)?).ok_or(WMMSCoreError::NumericOverflow)?;
+            (num, other.mantissa)
+        } else {
+            let den = other.mantissa.checked_mul(pow10((-exp) as u32)?).ok_or(WMMSCoreError::NumericOverflow)?;
+            (self.mantissa, den)
+        };
+        Ok(Self { mantissa: div_round(num, den, mode), scale })
+    }
+
+    /// Nearest fixed-point value, rounded with `mode`.
+    pub fn to_fixed64<const F: u32>(self, mode: RoundingMode) -> Result<FixedI64<F>> {
+        let num = self.mantissa.checked_mul(1i128 << F).ok_or(WMMSCoreError::NumericOverflow)?;
+        let raw = div_round(num, pow10(self.scale)?, mode);
+        i64::try_from(raw).map(FixedI64).map_err(|_| WMMSCoreError::NumericOverflow)
+    }
+
+    /// Decimal approximation of a fixed-point value with `scale` places.
+    pub fn from_fixed64<const F: u32>(v: FixedI64<F>, scale: u32, mode: RoundingMode) -> Result<Self> {
+        let num = (v.0 as i128).checked_mul(pow10(scale)?).ok_or(WMMSCoreError::NumericOverflow)?;
+        Self::new(div_round(num, 1i128 << F, mode), scale)
+    }
+}
+
+fn pow10(exp: u32) -> Result<i128> {
+    10i128.checked_pow(exp).ok_or(WMMSCoreError::NumericOverflow)
+}
+
+/// Integer division `n / d` rounded with `mode`.
+fn div_round(n: i128, d: i128, mode: RoundingMode) -> i128 {
+    let q = n / d;
+    let r = n % d;
+    if r == 0 {
+        return q;
+    }
+    let negative = (n < 0) != (d < 0);
+    let away = if negative { q - 1 } else { q + 1 };
+    let half_cmp = (r.unsigned_abs() * 2).cmp(&d.unsigned_abs());
+    match mode {
+        RoundingMode::Down => q,
+        RoundingMode::Floor => if negative { away } else { q },
+        RoundingMode::Ceiling => if negative { q } else { away },
+        RoundingMode::HalfUp => if half_cmp == Ordering::Less { q } else { away },
+        RoundingMode::HalfEven => match half_cmp {
+            Ordering::Less => q,
+            Ordering::Greater => away,
+            Ordering::Equal => if q % 2 == 0 { q } else { away },
+        },
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = (self.normalize(), other.normalize());
+        match a.aligned(b) {
+            Ok((x, y, _)) => x.cmp(&y),
+            // Rescaling overflowed: the operand with the smaller scale has the
+            // larger magnitude, so its sign decides.
+            Err(_) => {
+                let big = if a.scale < b.scale { a.mantissa } else { -b.mantissa };
+                if big > 0 { Ordering::Greater } else { Ordering::Less }
+            }
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let n = self.normalize();
+        n.mantissa.hash(state);
+        n.scale.hash(state);
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = match f.precision() {
+            Some(p) => self.round_dp(p as u32, RoundingMode::HalfEven).rescale(p as u32, RoundingMode::Down).unwrap_or(*self),
+            None => *self,
+        };
+        let digits = v.mantissa.unsigned_abs().to_string();
+        let scale = v.scale as usize;
+        let body = if scale == 0 {
+            digits
+        } else if digits.len() > scale {
+            format!("{}.{}", &digits[..digits.len() - scale], &digits[digits.len() - scale..])
+        } else {
+            format!("0.{digits:0>scale$}")
+        };
+        f.pad_integral(v.mantissa >= 0, "", &body)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = WMMSCoreError;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let invalid = || WMMSCoreError::InvalidValue(format!("invalid decimal: '{input}'"));
+        let s = input.trim();
+        let (neg, body) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int_str, frac_str) = body.split_once('.').unwrap_or((body, ""));
+        if int_str.is_empty() && frac_str.is_empty() {
+            return Err(invalid());
+        }
+        if !int_str.bytes().chain(frac_str.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let scale = frac_str.len() as u32;
+        if scale > Self::MAX_SCALE {
+            return Err(invalid());
+        }
+        let mut mantissa: i128 = 0;
+        for b in int_str.bytes().chain(frac_str.bytes()) {
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|m| m.checked_add((b - b'0') as i128))
+                .ok_or(WMMSCoreError::NumericOverflow)?;
+        }
+        Ok(Self { mantissa: if neg { -mantissa } else { mantissa }, scale })
+    }
+}
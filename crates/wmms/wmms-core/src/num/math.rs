@@ -0,0 +1,345 @@
+//! Deterministic transcendental functions for the fixed-point types.
+//!
+//! Every function here is computed with integer arithmetic in a Q32.32
+//! working format and lookup tables baked into the source, so results are
+//! bit-identical on every platform and compiler. Nothing goes through `f32`/`f64`.
+
+use crate::error::{Result, WMMSCoreError};
+
+use super::FixedU32;
+
+/// Fractional bits of the internal working format.
+pub(crate) const WORK_FRAC: u32 = 32;
+pub(crate) const ONE_Q32: i64 = 1 << WORK_FRAC;
+
+pub(crate) const LN_2_Q32: i64 = 2_977_044_472;
+pub(crate) const E_Q32: i64 = 11_674_931_555;
+pub(crate) const PI_Q32: i64 = 13_493_037_705;
+pub(crate) const HALF_PI_Q32: i64 = 6_746_518_852;
+pub(crate) const TAU_Q32: i64 = 26_986_075_409;
+
+/// Table steps per full turn (4 quadrants of `TRIG_STEPS`).
+const TRIG_STEPS: i64 = 256;
+/// `4 * TRIG_STEPS / TAU` in Q32, maps radians to table positions.
+const TURN_STEPS_PER_RAD_Q32: i64 = 699_970_842_190;
+
+/// `sin(i * PI / 512)` for `i` in `0..=256`, in Q2.30.
+static SIN_Q30: [i64; 257] = [
+    0, 6588356, 13176464, 19764076, 26350943, 32936819, 39521455, 46104602,
+    52686014, 59265442, 65842639, 72417357, 78989349, 85558366, 92124163, 98686491,
+    105245103, 111799753, 118350194, 124896179, 131437462, 137973796, 144504935, 151030634,
+    157550647, 164064728, 170572633, 177074115, 183568930, 190056834, 196537583, 203010932,
+    209476638, 215934457, 222384147, 228825464, 235258165, 241682010, 248096755, 254502159,
+    260897982, 267283981, 273659918, 280025552, 286380643, 292724951, 299058239, 305380268,
+    311690799, 317989595, 324276419, 330551034, 336813204, 343062693, 349299266, 355522689,
+    361732726, 367929144, 374111709, 380280190, 386434353, 392573967, 398698801, 404808624,
+    410903207, 416982319, 423045732, 429093217, 435124548, 441139496, 447137835, 453119340,
+    459083786, 465030947, 470960600, 476872522, 482766489, 488642281, 494499676, 500338453,
+    506158392, 511959275, 517740883, 523502998, 529245404, 534967884, 540670223, 546352205,
+    552013618, 557654248, 563273883, 568872310, 574449320, 580004702, 585538248, 591049748,
+    596538995, 602005783, 607449906, 612871159, 618269338, 623644239, 628995660, 634323400,
+    639627258, 644907034, 650162530, 655393548, 660599890, 665781362, 670937767, 676068911,
+    681174602, 686254647, 691308855, 696337036, 701339000, 706314559, 711263525, 716185713,
+    721080937, 725949013, 730789757, 735602987, 740388522, 745146182, 749875788, 754577161,
+    759250125, 763894504, 768510122, 773096806, 777654384, 782182683, 786681534, 791150767,
+    795590213, 799999706, 804379079, 808728167, 813046808, 817334838, 821592095, 825818421,
+    830013654, 834177638, 838310216, 842411232, 846480531, 850517961, 854523370, 858496606,
+    862437520, 866345964, 870221790, 874064853, 877875009, 881652112, 885396022, 889106597,
+    892783698, 896427186, 900036924, 903612776, 907154608, 910662286, 914135678, 917574653,
+    920979082, 924348837, 927683790, 930983817, 934248793, 937478595, 940673101, 943832191,
+    946955747, 950043650, 953095785, 956112036, 959092290, 962036435, 964944360, 967815955,
+    970651112, 973449725, 976211688, 978936898, 981625251, 984276646, 986890984, 989468165,
+    992008094, 994510675, 996975812, 999403415, 1001793390, 1004145648, 1006460100, 1008736660,
+    1010975242, 1013175761, 1015338134, 1017462281, 1019548121, 1021595575, 1023604567, 1025575020,
+    1027506862, 1029400018, 1031254418, 1033069992, 1034846671, 1036584389, 1038283080, 1039942680,
+    1041563127, 1043144360, 1044686319, 1046188946, 1047652185, 1049075980, 1050460278, 1051805027,
+    1053110176, 1054375676, 1055601479, 1056787540, 1057933813, 1059040255, 1060106826, 1061133483,
+    1062120190, 1063066909, 1063973603, 1064840240, 1065666786, 1066453210, 1067199483, 1067905576,
+    1068571464, 1069197120, 1069782521, 1070327646, 1070832474, 1071296985, 1071721163, 1072104991,
+    1072448455, 1072751542, 1073014240, 1073236540, 1073418433, 1073559913, 1073660973, 1073721611,
+    1073741824,
+];
+
+/// `atan(i / 256)` for `i` in `0..=256`, in Q2.30.
+static ATAN_Q30: [i64; 257] = [
+    0, 4194283, 8388437, 12582336, 16775851, 20968854, 25161218, 29352814,
+    33543516, 37733196, 41921726, 46108981, 50294833, 54479155, 58661822, 62842708,
+    67021687, 71198634, 75373424, 79545932, 83716036, 87883610, 92048532, 96210679,
+    100369930, 104526161, 108679253, 112829084, 116975536, 121118487, 125257820, 129393416,
+    133525159, 137652930, 141776614, 145896097, 150011262, 154121996, 158228185, 162329719,
+    166426484, 170518371, 174605269, 178687069, 182763663, 186834944, 190900805, 194961140,
+    199015846, 203064818, 207107953, 211145151, 215176309, 219201328, 223220110, 227232556,
+    231238569, 235238055, 239230917, 243217063, 247196400, 251168835, 255134279, 259092643,
+    263043837, 266987774, 270924369, 274853536, 278775192, 282689253, 286595638, 290494267,
+    294385059, 298267937, 302142824, 306009643, 309868320, 313718782, 317560955, 321394768,
+    325220151, 329037035, 332845353, 336645037, 340436023, 344218245, 347991640, 351756148,
+    355511705, 359258254, 362995735, 366724092, 370443267, 374153206, 377853855, 381545162,
+    385227074, 388899541, 392562515, 396215946, 399859787, 403493994, 407118521, 410733324,
+    414338361, 417933591, 421518973, 425094468, 428660037, 432215645, 435761254, 439296830,
+    442822340, 446337750, 449843028, 453338145, 456823070, 460297774, 463762232, 467216414,
+    470660297, 474093856, 477517067, 480929907, 484332355, 487724391, 491105994, 494477146,
+    497837829, 501188027, 504527723, 507856902, 511175551, 514483656, 517781204, 521068185,
+    524344587, 527610402, 530865619, 534110231, 537344232, 540567613, 543780370, 546982499,
+    550173994, 553354853, 556525073, 559684652, 562833591, 565971887, 569099543, 572216558,
+    575322936, 578418678, 581503788, 584578271, 587642129, 590695370, 593737999, 596770023,
+    599791448, 602802283, 605802536, 608792216, 611771334, 614739898, 617697921, 620645413,
+    623582386, 626508854, 629424828, 632330323, 635225352, 638109930, 640984073, 643847795,
+    646701114, 649544044, 652376604, 655198810, 658010682, 660812236, 663603492, 666384468,
+    669155185, 671915663, 674665921, 677405981, 680135863, 682855589, 685565182, 688264663,
+    690954054, 693633380, 696302662, 698961924, 701611191, 704250487, 706879836, 709499262,
+    712108791, 714708448, 717298260, 719878250, 722448447, 725008876, 727559563, 730100536,
+    732631822, 735153448, 737665442, 740167831, 742660643, 745143906, 747617650, 750081902,
+    752536690, 754982045, 757417995, 759844569, 762261796, 764669707, 767068330, 769457696,
+    771837835, 774208776, 776570551, 778923188, 781266719, 783601175, 785926586, 788242982,
+    790550395, 792848855, 795138394, 797419043, 799690833, 801953796, 804207961, 806453363,
+    808690030, 810917996, 813137292, 815347949, 817549999, 819743474, 821928406, 824104826,
+    826272767, 828432260, 830583337, 832726030, 834860371, 836986393, 839104126, 841213603,
+    843314857,
+];
+
+/// Rounds a Q32 value to `to` fractional bits, half away from zero.
+pub(crate) fn rescale_q32(v: i128, to: u32) -> i128 {
+    if to >= WORK_FRAC {
+        return v << (to - WORK_FRAC);
+    }
+    let shift = WORK_FRAC - to;
+    let half = 1i128 << (shift - 1);
+    if v >= 0 { (v + half) >> shift } else { -((-v + half) >> shift) }
+}
+
+#[inline]
+fn mul_q32(a: i128, b: i128) -> Option<i128> {
+    a.checked_mul(b).map(|p| p >> WORK_FRAC)
+}
+
+/// Interpolates a 257-entry Q30 table at a Q32 position in `[0, 256]`.
+fn lookup_q30(table: &[i64; 257], pos_q32: i64) -> i64 {
+    let i = (pos_q32 >> WORK_FRAC) as usize;
+    if i >= 256 {
+        return table[256];
+    }
+    let frac = pos_q32 & (ONE_Q32 - 1);
+    let (a, b) = (table[i], table[i + 1]);
+    a + (((b - a) as i128 * frac as i128) >> WORK_FRAC) as i64
+}
+
+/// Square root of a non-negative Q32 value, rounded to nearest.
+pub(crate) fn sqrt_q32(v: i64) -> Option<i64> {
+    if v < 0 {
+        return None;
+    }
+    let n = (v as u128) << WORK_FRAC;
+    let r = n.isqrt();
+    let r = if n - r * r > r { r + 1 } else { r };
+    Some(r as i64)
+}
+
+/// `e^x` for a Q32 input. Returns `None` when the result cannot fit in 64 integer bits.
+pub(crate) fn exp_q32(x: i64) -> Option<i128> {
+    // x = k*ln2 + r with |r| <= ln2/2
+    let k = {
+        let half = LN_2_Q32 / 2;
+        if x >= 0 { (x + half) / LN_2_Q32 } else { (x - half) / LN_2_Q32 }
+    };
+    if k > 64 {
+        return None;
+    }
+    if k < -64 {
+        return Some(0);
+    }
+    let r = (x - k * LN_2_Q32) as i128;
+
+    let mut sum = ONE_Q32 as i128;
+    let mut term = ONE_Q32 as i128;
+    let mut n = 1i128;
+    while term != 0 {
+        term = ((term * r) >> WORK_FRAC) / n;
+        sum += term;
+        n += 1;
+    }
+
+    Some(if k >= 0 { sum << k } else { (sum + (1i128 << (-k - 1))) >> -k })
+}
+
+/// Natural logarithm of a positive Q32 value.
+pub(crate) fn ln_q32(v: i64) -> Option<i64> {
+    if v <= 0 {
+        return None;
+    }
+    // v = m * 2^k with m in [1, 2)
+    let msb = 63 - v.leading_zeros() as i64;
+    let k = msb - WORK_FRAC as i64;
+    let m = if k >= 0 { (v >> k) as i128 } else { (v << -k) as i128 };
+
+    // ln(m) = 2 * atanh(z), z = (m - 1) / (m + 1) <= 1/3
+    let one = ONE_Q32 as i128;
+    let z = ((m - one) << WORK_FRAC) / (m + one);
+    let z2 = (z * z) >> WORK_FRAC;
+    let mut term = z;
+    let mut sum = 0i128;
+    let mut d = 1i128;
+    while term != 0 {
+        sum += term / d;
+        term = (term * z2) >> WORK_FRAC;
+        d += 2;
+    }
+
+    Some((k as i128 * LN_2_Q32 as i128 + 2 * sum) as i64)
+}
+
+/// `x^n` for a Q32 base and integer exponent.
+pub(crate) fn powi_q32(x: i64, n: i32) -> Option<i128> {
+    let one = ONE_Q32 as i128;
+    let mut result = one;
+    let mut base = x as i128;
+    let mut e = n.unsigned_abs();
+    while e > 0 {
+        if e & 1 == 1 {
+            result = mul_q32(result, base)?;
+            i64::try_from(result).ok()?;
+        }
+        e >>= 1;
+        if e > 0 {
+            base = mul_q32(base, base)?;
+            i64::try_from(base).ok()?;
+        }
+    }
+    if n < 0 {
+        if result == 0 {
+            return None;
+        }
+        result = (one << WORK_FRAC) / result;
+    }
+    Some(result)
+}
+
+/// Sine of a Q32 angle in radians, in Q32.
+pub(crate) fn sin_q32(angle: i64) -> i64 {
+    let phase = angle.rem_euclid(TAU_Q32) as i128;
+    let pos = ((phase * TURN_STEPS_PER_RAD_Q32 as i128) >> WORK_FRAC) as i64;
+    sin_at_pos(pos)
+}
+
+/// Cosine of a Q32 angle in radians, in Q32.
+pub(crate) fn cos_q32(angle: i64) -> i64 {
+    let phase = angle.rem_euclid(TAU_Q32) as i128;
+    let pos = ((phase * TURN_STEPS_PER_RAD_Q32 as i128) >> WORK_FRAC) as i64;
+    sin_at_pos(pos + (TRIG_STEPS << WORK_FRAC))
+}
+
+/// Sine at a Q32 table position, where a full turn is `4 * TRIG_STEPS`.
+fn sin_at_pos(pos: i64) -> i64 {
+    let quarter = TRIG_STEPS << WORK_FRAC;
+    let pos = pos.rem_euclid(4 * quarter);
+    let within = pos % quarter;
+    let v = match pos / quarter {
+        0 => lookup_q30(&SIN_Q30, within),
+        1 => lookup_q30(&SIN_Q30, quarter - within),
+        2 => -lookup_q30(&SIN_Q30, within),
+        _ => -lookup_q30(&SIN_Q30, quarter - within),
+    };
+    v << 2
+}
+
+/// Four-quadrant arctangent of Q32 inputs, in Q32 radians within `[-PI, PI]`.
+pub(crate) fn atan2_q32(y: i64, x: i64) -> i64 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let (ax, ay) = (x.unsigned_abs() as u128, y.unsigned_abs() as u128);
+    let (num, den) = if ay <= ax { (ay, ax) } else { (ax, ay) };
+    let ratio = ((num << WORK_FRAC) / den) as i64;
+    let mut a = lookup_q30(&ATAN_Q30, ratio * TRIG_STEPS) << 2;
+    if ay > ax {
+        a = HALF_PI_Q32 - a;
+    }
+    if x < 0 {
+        a = PI_Q32 - a;
+    }
+    if y < 0 { -a } else { a }
+}
+
+impl<const FRAC: u32> FixedU32<FRAC> {
+    pub const PI: Self = Self::from_q32_const(PI_Q32);
+    pub const HALF_PI: Self = Self::from_q32_const(HALF_PI_Q32);
+    pub const TAU: Self = Self::from_q32_const(TAU_Q32);
+    pub const E: Self = Self::from_q32_const(E_Q32);
+    pub const LN_2: Self = Self::from_q32_const(LN_2_Q32);
+
+    const fn from_q32_const(v: i64) -> Self {
+        let shift = WORK_FRAC - FRAC;
+        Self(((v + (1 << (shift - 1))) >> shift) as i32)
+    }
+
+    #[inline]
+    fn to_q32(self) -> i64 {
+        (self.0 as i64) << (WORK_FRAC - FRAC)
+    }
+
+    fn saturate_q32(v: i128) -> Self {
+        let raw = rescale_q32(v, FRAC).clamp(i32::MIN as i128, i32::MAX as i128);
+        Self(raw as i32)
+    }
+
+    fn from_q32(v: i128) -> Result<Self> {
+        i32::try_from(rescale_q32(v, FRAC))
+            .map(Self)
+            .map_err(|_| WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn sqrt(self) -> Result<Self> {
+        let r = sqrt_q32(self.to_q32())
+            .ok_or_else(|| WMMSCoreError::InvalidValue("sqrt of a negative number".into()))?;
+        Self::from_q32(r as i128)
+    }
+
+    pub fn exp(self) -> Result<Self> {
+        let r = exp_q32(self.to_q32()).ok_or(WMMSCoreError::NumericOverflow)?;
+        Self::from_q32(r)
+    }
+
+    pub fn ln(self) -> Result<Self> {
+        let r = ln_q32(self.to_q32())
+            .ok_or_else(|| WMMSCoreError::InvalidValue("ln of a non-positive number".into()))?;
+        Self::from_q32(r as i128)
+    }
+
+    /// Integer power by repeated squaring.
+    pub fn powi(self, n: i32) -> Result<Self> {
+        let r = powi_q32(self.to_q32(), n).ok_or(WMMSCoreError::NumericOverflow)?;
+        Self::from_q32(r)
+    }
+
+    /// Real power `self^exp`. Negative bases require an integral exponent.
+    pub fn pow(self, exp: Self) -> Result<Self> {
+        if exp.fract() == Self::ZERO {
+            return self.powi(exp.to_i32_floor());
+        }
+        if self.0 <= 0 {
+            return if self.0 == 0 && exp.0 > 0 {
+                Ok(Self::ZERO)
+            } else {
+                Err(WMMSCoreError::InvalidValue("fractional power of a non-positive number".into()))
+            };
+        }
+        let ln = ln_q32(self.to_q32()).ok_or(WMMSCoreError::NumericOverflow)?;
+        let y = (exp.to_q32() as i128 * ln as i128) >> WORK_FRAC;
+        let y = i64::try_from(y).map_err(|_| WMMSCoreError::NumericOverflow)?;
+        Self::from_q32(exp_q32(y).ok_or(WMMSCoreError::NumericOverflow)?)
+    }
+
+    /// Sine of an angle in radians.
+    pub fn sin(self) -> Self {
+        Self::saturate_q32(sin_q32(self.to_q32()) as i128)
+    }
+
+    /// Cosine of an angle in radians.
+    pub fn cos(self) -> Self {
+        Self::saturate_q32(cos_q32(self.to_q32()) as i128)
+    }
+
+    /// Angle of the vector `(x, y)` in radians, within `[-PI, PI]`.
+    pub fn atan2(y: Self, x: Self) -> Self {
+        Self::saturate_q32(atan2_q32(y.to_q32(), x.to_q32()) as i128)
+    }
+}
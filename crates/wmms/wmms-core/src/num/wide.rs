@@ -0,0 +1,283 @@
+use core::fmt;
+use core::str::FromStr;
+
+use crate::error::{Result, WMMSCoreError};
+
+use super::math::{self, WORK_FRAC};
+use super::{FixedU32, format_fixed, format_fixed_shortest, pad_signed, parse_fixed};
+
+/// i64-backed fixed-point number for large-magnitude world values
+/// (populations, treasuries, distances, cosmology constants).
+///
+/// Unlike `FixedU32`, arithmetic never saturates: every operation that can
+/// leave the representable range reports `WMMSCoreError::NumericOverflow`.
+#[repr(transparent)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct FixedI64<const FRAC_BITS: u32>(pub i64);
+
+pub type Q32_32 = FixedI64<32>;
+
+impl<const FRAC: u32> FixedI64<FRAC> {
+    pub const SCALE: i128 = 1i128 << FRAC;
+    pub const FRAC_MASK: i64 = (Self::SCALE - 1) as i64;
+
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(Self::SCALE as i64);
+    pub const MIN: Self = Self(i64::MIN);
+    pub const MAX: Self = Self(i64::MAX);
+    /// Smallest positive representable value.
+    pub const EPSILON: Self = Self(1);
+
+    #[inline]
+    pub const fn from_raw(raw: i64) -> Self {
+        Self(raw)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> i64 {
+        self.0
+    }
+
+    #[inline]
+    fn from_wide(raw: i128) -> Result<Self> {
+        i64::try_from(raw).map(Self).map_err(|_| WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn from_i64(v: i64) -> Result<Self> {
+        Self::from_wide(v as i128 * Self::SCALE)
+    }
+
+    /// Integer part, rounded toward negative infinity.
+    #[inline]
+    pub fn to_i64_floor(self) -> i64 {
+        self.0 >> FRAC
+    }
+
+    /// Integer part, rounded to nearest with ties away from zero.
+    pub fn to_i64_round(self) -> i64 {
+        rescale(self.0 as i128, FRAC, 0) as i64
+    }
+
+    /// Lossy conversion for display and tooling only; never feed the result back into simulation.
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    // Quantized conversion from f64, rounding to nearest representable value
+    pub fn from_f64_quantized(v: f64) -> Result<Self> {
+        let scaled = v * Self::SCALE as f64;
+        if !scaled.is_finite() || scaled >= i64::MAX as f64 || scaled < i64::MIN as f64 {
+            return Err(WMMSCoreError::NumericOverflow);
+        }
+        Ok(Self(super::round_half_away(scaled) as i64))
+    }
+
+    // ----- checked arithmetic -----
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        i64::try_from((self.0 as i128 * other.0 as i128) >> FRAC).ok().map(Self)
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.0 == 0 {
+            return None;
+        }
+        i64::try_from(((self.0 as i128) << FRAC) / other.0 as i128).ok().map(Self)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Self)
+    }
+
+    pub fn checked_abs(self) -> Option<Self> {
+        self.0.checked_abs().map(Self)
+    }
+
+    // ----- fallible arithmetic -----
+
+    pub fn try_add(self, other: Self) -> Result<Self> {
+        self.checked_add(other).ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn try_sub(self, other: Self) -> Result<Self> {
+        self.checked_sub(other).ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn try_mul(self, other: Self) -> Result<Self> {
+        self.checked_mul(other).ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn try_div(self, other: Self) -> Result<Self> {
+        if other.0 == 0 {
+            return Err(WMMSCoreError::InvalidValue("Division by zero".into()));
+        }
+        self.checked_div(other).ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn try_neg(self) -> Result<Self> {
+        self.checked_neg().ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    pub fn try_abs(self) -> Result<Self> {
+        self.checked_abs().ok_or(WMMSCoreError::NumericOverflow)
+    }
+
+    // ----- helpers -----
+
+    #[inline]
+    pub fn signum(self) -> i64 {
+        self.0.signum()
+    }
+
+    #[inline]
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn floor(self) -> Self {
+        Self(self.0 & !Self::FRAC_MASK)
+    }
+
+    pub fn ceil(self) -> Result<Self> {
+        if self.0 & Self::FRAC_MASK == 0 {
+            Ok(self)
+        } else {
+            Self::from_wide((self.0 & !Self::FRAC_MASK) as i128 + Self::SCALE)
+        }
+    }
+
+    /// Rounds to the nearest integer, ties away from zero.
+    pub fn round(self) -> Result<Self> {
+        Self::from_wide(self.to_i64_round() as i128 * Self::SCALE)
+    }
+
+    /// Fractional part, always in `[0, 1)`.
+    pub fn fract(self) -> Self {
+        Self(self.0 & Self::FRAC_MASK)
+    }
+
+    pub fn clamp(self, min: Self, max: Self) -> Self {
+        Ord::clamp(self, min, max)
+    }
+
+    /// Linear interpolation `a + (b - a) * t`.
+    pub fn lerp(a: Self, b: Self, t: Self) -> Result<Self> {
+        let delta = b.0 as i128 - a.0 as i128;
+        let step = delta.checked_mul(t.0 as i128).ok_or(WMMSCoreError::NumericOverflow)? >> FRAC;
+        Self::from_wide(a.0 as i128 + step)
+    }
+
+    // ----- Q format conversions -----
+
+    pub fn convert<const TO: u32>(self) -> Result<FixedI64<TO>> {
+        FixedI64::<TO>::from_wide(rescale(self.0 as i128, FRAC, TO))
+    }
+
+    pub fn from_fixed32<const F: u32>(v: FixedU32<F>) -> Result<Self> {
+        Self::from_wide(rescale(v.0 as i128, F, FRAC))
+    }
+
+    pub fn to_fixed32<const F: u32>(self) -> Result<FixedU32<F>> {
+        i32::try_from(rescale(self.0 as i128, FRAC, F))
+            .map(FixedU32)
+            .map_err(|_| WMMSCoreError::NumericOverflow)
+    }
+
+    // ----- math -----
+
+    fn to_q32(self) -> Result<i64> {
+        i64::try_from(rescale(self.0 as i128, FRAC, WORK_FRAC)).map_err(|_| WMMSCoreError::NumericOverflow)
+    }
+
+    fn from_q32(v: i128) -> Result<Self> {
+        Self::from_wide(math::rescale_q32(v, FRAC))
+    }
+
+    pub fn sqrt(self) -> Result<Self> {
+        let r = math::sqrt_q32(self.to_q32()?)
+            .ok_or_else(|| WMMSCoreError::InvalidValue("sqrt of a negative number".into()))?;
+        Self::from_q32(r as i128)
+    }
+
+    pub fn exp(self) -> Result<Self> {
+        Self::from_q32(math::exp_q32(self.to_q32()?).ok_or(WMMSCoreError::NumericOverflow)?)
+    }
+
+    pub fn ln(self) -> Result<Self> {
+        let r = math::ln_q32(self.to_q32()?)
+            .ok_or_else(|| WMMSCoreError::InvalidValue("ln of a non-positive number".into()))?;
+        Self::from_q32(r as i128)
+    }
+
+    pub fn powi(self, n: i32) -> Result<Self> {
+        Self::from_q32(math::powi_q32(self.to_q32()?, n).ok_or(WMMSCoreError::NumericOverflow)?)
+    }
+
+    /// Real power `self^exp`. Negative bases require an integral exponent.
+    pub fn pow(self, exp: Self) -> Result<Self> {
+        if exp.fract() == Self::ZERO {
+            let n = i32::try_from(exp.to_i64_floor()).map_err(|_| WMMSCoreError::NumericOverflow)?;
+            return self.powi(n);
+        }
+        if self.0 <= 0 {
+            return if self.0 == 0 && exp.0 > 0 {
+                Ok(Self::ZERO)
+            } else {
+                Err(WMMSCoreError::InvalidValue("fractional power of a non-positive number".into()))
+            };
+        }
+        let ln = math::ln_q32(self.to_q32()?).ok_or(WMMSCoreError::NumericOverflow)?;
+        let y = (exp.to_q32()? as i128 * ln as i128) >> WORK_FRAC;
+        let y = i64::try_from(y).map_err(|_| WMMSCoreError::NumericOverflow)?;
+        Self::from_q32(math::exp_q32(y).ok_or(WMMSCoreError::NumericOverflow)?)
+    }
+
+    pub fn sin(self) -> Result<Self> {
+        Self::from_q32(math::sin_q32(self.to_q32()?) as i128)
+    }
+
+    pub fn cos(self) -> Result<Self> {
+        Self::from_q32(math::cos_q32(self.to_q32()?) as i128)
+    }
+
+    pub fn atan2(y: Self, x: Self) -> Result<Self> {
+        Self::from_q32(math::atan2_q32(y.to_q32()?, x.to_q32()?) as i128)
+    }
+}
+
+/// Moves a raw value between fractional widths, rounding half away from zero.
+fn rescale(raw: i128, from: u32, to: u32) -> i128 {
+    if to >= from {
+        raw << (to - from)
+    } else {
+        let shift = from - to;
+        let half = 1i128 << (shift - 1);
+        if raw >= 0 { (raw + half) >> shift } else { -((-raw + half) >> shift) }
+    }
+}
+
+impl<const FRAC: u32> fmt::Display for FixedI64<FRAC> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match f.precision() {
+            Some(p) => format_fixed(self.0 as i128, FRAC, p as u32),
+            None => format_fixed_shortest(self.0 as i128, FRAC),
+        };
+        pad_signed(f, &s)
+    }
+}
+
+impl<const FRAC: u32> FromStr for FixedI64<FRAC> {
+    type Err = WMMSCoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_wide(parse_fixed(s, FRAC)?)
+    }
+}
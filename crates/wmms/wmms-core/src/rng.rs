@@ -1,9 +1,57 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
 use rand::{CryptoRng, RngCore, SeedableRng};
 use rand_chacha::ChaCha12Rng;
 
+use crate::error::{Result, WMMSCoreError};
+use crate::num::FixedU32;
+
+pub mod dice;
+pub mod streams;
+
 #[derive(Clone, Debug)]
 pub struct DetRng(ChaCha12Rng);
 
+/// Exact position of a `DetRng` in its stream, enough to resume it bit-for-bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetRngState {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    /// Number of 32-bit words already consumed.
+    pub word_pos: u128,
+}
+
+impl DetRngState {
+    pub const ENCODED_LEN: usize = 32 + 8 + 16;
+
+    /// Fixed little-endian encoding for session files and journals.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut out = [0u8; Self::ENCODED_LEN];
+        out[..32].copy_from_slice(&self.seed);
+        out[32..40].copy_from_slice(&self.stream.to_le_bytes());
+        out[40..].copy_from_slice(&self.word_pos.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(WMMSCoreError::InvalidValue(format!(
+                "rng state must be {} bytes, got {}",
+                Self::ENCODED_LEN,
+                bytes.len()
+            )));
+        }
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&bytes[..32]);
+        let stream = u64::from_le_bytes(bytes[32..40].try_into().expect("checked length"));
+        let word_pos = u128::from_le_bytes(bytes[40..].try_into().expect("checked length"));
+        Ok(Self { seed, stream, word_pos })
+    }
+}
+
 impl DetRng {
     pub fn from_seed_u64(seed: u64) -> Self {
         let mut s = [0u8; 32];
@@ -11,11 +59,155 @@ impl DetRng {
         Self(ChaCha12Rng::from_seed(s))
     }
 
+    pub fn from_seed_bytes(seed: [u8; 32]) -> Self {
+        Self(ChaCha12Rng::from_seed(seed))
+    }
+
+    /// Stream derived from a session seed and a stable tag, independent of
+    /// any other stream's consumption or creation order.
+    pub fn derive(session_seed: u64, tag: &str) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"wmms.rng.stream:");
+        hasher.update(&session_seed.to_le_bytes());
+        hasher.update(tag.as_bytes());
+        Self::from_seed_bytes(*hasher.finalize().as_bytes())
+    }
+
+    pub fn state(&self) -> DetRngState {
+        DetRngState {
+            seed: self.0.get_seed(),
+            stream: self.0.get_stream(),
+            word_pos: self.0.get_word_pos(),
+        }
+    }
+
+    pub fn from_state(state: &DetRngState) -> Self {
+        let mut rng = ChaCha12Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(state.word_pos);
+        Self(rng)
+    }
+
     // Deterministically splits the RNG into two independent RNGs
     pub fn split(&mut self, tag: u64) -> Self {
-        let mut seed = self.next_u64() ^ tag;
+        let seed = self.next_u64() ^ tag;
         DetRng::from_seed_u64(seed)
     }
+
+    // ----- Distributions -----
+    //
+    // Everything below only consumes raw `next_u32`/`next_u64` words and uses
+    // algorithms defined here, never `rand::distr`, so results stay
+    // bit-identical across `rand` upgrades.
+
+    /// Uniform integer in `[0, n)` (Lemire's multiply-and-reject, unbiased).
+    /// Panics if `n` is zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "DetRng::below called with n = 0");
+        let mut m = self.next_u64() as u128 * n as u128;
+        if (m as u64) < n {
+            let threshold = n.wrapping_neg() % n;
+            while (m as u64) < threshold {
+                m = self.next_u64() as u128 * n as u128;
+            }
+        }
+        (m >> 64) as u64
+    }
+
+    /// Uniform integer in an inclusive range. Panics if the range is empty.
+    pub fn range_i64(&mut self, range: RangeInclusive<i64>) -> i64 {
+        let (lo, hi) = range.into_inner();
+        assert!(lo <= hi, "DetRng::range_i64 called with an empty range");
+        let span = (hi as i128 - lo as i128) as u128 + 1;
+        if span > u64::MAX as u128 {
+            return self.next_u64() as i64;
+        }
+        (lo as i128 + self.below(span as u64) as i128) as i64
+    }
+
+    /// Uniform integer in an inclusive range. Panics if the range is empty.
+    pub fn range_u32(&mut self, range: RangeInclusive<u32>) -> u32 {
+        let (lo, hi) = range.into_inner();
+        assert!(lo <= hi, "DetRng::range_u32 called with an empty range");
+        lo + self.below(hi as u64 - lo as u64 + 1) as u32
+    }
+
+    /// Uniform fixed-point value in `[0, 1)`.
+    pub fn unit_fixed<const F: u32>(&mut self) -> FixedU32<F> {
+        FixedU32(self.next_u32().checked_shr(32 - F).unwrap_or(0) as i32)
+    }
+
+    /// Uniform fixed-point value in `[lo, hi)`, or `lo` if the range is empty.
+    pub fn range_fixed<const F: u32>(&mut self, lo: FixedU32<F>, hi: FixedU32<F>) -> FixedU32<F> {
+        if hi <= lo {
+            return lo;
+        }
+        let span = (hi.0 as i64 - lo.0 as i64) as u64;
+        FixedU32((lo.0 as i64 + self.below(span) as i64) as i32)
+    }
+
+    /// `true` with probability `p` (clamped to `[0, 1]`).
+    pub fn chance<const F: u32>(&mut self, p: FixedU32<F>) -> bool {
+        self.unit_fixed::<F>() < p
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.below(items.len() as u64) as usize)
+    }
+
+    /// Index picked proportionally to `weights`. `None` if every weight is zero.
+    pub fn weighted_index(&mut self, weights: &[u32]) -> Option<usize> {
+        let total: u64 = weights.iter().map(|w| *w as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = self.below(total);
+        for (i, w) in weights.iter().enumerate() {
+            let w = *w as u64;
+            if roll < w {
+                return Some(i);
+            }
+            roll -= w;
+        }
+        None
+    }
+
+    pub fn choose_weighted<'a, T>(&mut self, items: &'a [T], weight: impl Fn(&T) -> u32) -> Option<&'a T> {
+        let weights: Vec<u32> = items.iter().map(weight).collect();
+        self.weighted_index(&weights).map(|i| &items[i])
+    }
+
+    /// Fisher-Yates shuffle, walking from the back.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Uniform sample of up to `k` items in one pass (Algorithm R).
+    /// Selected items keep the order in which they were seen.
+    pub fn sample_reservoir<T, I: IntoIterator<Item = T>>(&mut self, iter: I, k: usize) -> Vec<T> {
+        let mut reservoir: Vec<(usize, T)> = Vec::with_capacity(k);
+        if k == 0 {
+            return Vec::new();
+        }
+        for (i, item) in iter.into_iter().enumerate() {
+            if i < k {
+                reservoir.push((i, item));
+            } else {
+                let j = self.below(i as u64 + 1) as usize;
+                if j < k {
+                    reservoir[j] = (i, item);
+                }
+            }
+        }
+        reservoir.sort_by_key(|(i, _)| *i);
+        reservoir.into_iter().map(|(_, item)| item).collect()
+    }
 }
 
 impl RngCore for DetRng {
@@ -36,3 +228,17 @@ impl RngCore for DetRng {
 }
 
 impl CryptoRng for DetRng {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DetRng {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        self.state().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DetRng {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        DetRngState::deserialize(deserializer).map(|s| DetRng::from_state(&s))
+    }
+}
@@ -0,0 +1,246 @@
+//! Dice-notation expressions such as `3d6+2`, `4d6kh3` or `2d20kl1 - 1`.
+//!
+//! Dice are rolled left to right with `DetRng::below`, and kept/dropped dice
+//! are selected with a stable sort, so a given seed always yields the same
+//! rolls and the same total.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::error::{Result, WMMSCoreError};
+
+use super::DetRng;
+
+/// Upper bounds that keep authored expressions from stalling a step.
+pub const MAX_DICE: u32 = 1_000;
+pub const MAX_SIDES: u32 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+    DropHighest(u32),
+    DropLowest(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiceTerm {
+    Dice { count: u32, sides: u32, keep: Option<Keep> },
+    Const(i64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DiceExpr {
+    /// Terms with their sign (`true` for `-`).
+    terms: Vec<(bool, DiceTerm)>,
+}
+
+/// One die rolled while evaluating an expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DieRoll {
+    pub term: usize,
+    pub value: u32,
+    pub kept: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiceRoll {
+    pub total: i64,
+    pub dice: Vec<DieRoll>,
+}
+
+impl DiceExpr {
+    pub fn parse(input: &str) -> Result<Self> {
+        let invalid = |why: &str| WMMSCoreError::InvalidValue(format!("invalid dice expression '{input}': {why}"));
+        let compact: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+        let compact = compact.to_ascii_lowercase();
+        if compact.is_empty() {
+            return Err(invalid("empty"));
+        }
+
+        let mut terms = Vec::new();
+        let mut rest = compact.as_str();
+        let mut negative = false;
+        if let Some(r) = rest.strip_prefix('-') {
+            negative = true;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('+') {
+            rest = r;
+        }
+
+        loop {
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let (token, tail) = rest.split_at(end);
+            if token.is_empty() {
+                return Err(invalid("missing term"));
+            }
+            terms.push((negative, Self::parse_term(token).map_err(|why| invalid(&why))?));
+
+            let Some(op) = tail.chars().next() else { break };
+            negative = op == '-';
+            rest = &tail[1..];
+        }
+
+        Ok(Self { terms })
+    }
+
+    fn parse_term(token: &str) -> core::result::Result<DiceTerm, String> {
+        let Some((count, rest)) = token.split_once('d') else {
+            return token.parse().map(DiceTerm::Const).map_err(|_| format!("bad constant '{token}'"));
+        };
+        let count: u32 = if count.is_empty() { 1 } else { count.parse().map_err(|_| format!("bad dice count '{count}'"))? };
+
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '%').unwrap_or(rest.len());
+        let (sides, modifier) = rest.split_at(split);
+        let sides: u32 = match sides {
+            "%" => 100,
+            s => s.parse().map_err(|_| format!("bad die size '{s}'"))?,
+        };
+
+        let keep = if modifier.is_empty() {
+            None
+        } else {
+            let (kind, n) = modifier.split_at(2.min(modifier.len()));
+            let n: u32 = n.parse().map_err(|_| format!("bad keep count in '{modifier}'"))?;
+            Some(match kind {
+                "kh" => Keep::Highest(n),
+                "kl" => Keep::Lowest(n),
+                "dh" => Keep::DropHighest(n),
+                "dl" => Keep::DropLowest(n),
+                _ => return Err(format!("unknown modifier '{modifier}'")),
+            })
+        };
+
+        if count == 0 || count > MAX_DICE {
+            return Err(format!("dice count must be in 1..={MAX_DICE}"));
+        }
+        if sides == 0 || sides > MAX_SIDES {
+            return Err(format!("die size must be in 1..={MAX_SIDES}"));
+        }
+        Ok(DiceTerm::Dice { count, sides, keep })
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = (bool, &DiceTerm)> {
+        self.terms.iter().map(|(neg, t)| (*neg, t))
+    }
+
+    pub fn roll(&self, rng: &mut DetRng) -> DiceRoll {
+        let mut total = 0i64;
+        let mut dice = Vec::new();
+        for (term_idx, (negative, term)) in self.terms.iter().enumerate() {
+            let value = match *term {
+                DiceTerm::Const(c) => c,
+                DiceTerm::Dice { count, sides, keep } => {
+                    let start = dice.len();
+                    for _ in 0..count {
+                        let value = rng.below(sides as u64) as u32 + 1;
+                        dice.push(DieRoll { term: term_idx, value, kept: true });
+                    }
+                    let rolled = &mut dice[start..];
+                    if let Some(keep) = keep {
+                        apply_keep(rolled, keep);
+                    }
+                    rolled.iter().filter(|d| d.kept).map(|d| d.value as i64).sum()
+                }
+            };
+            total = if *negative { total.saturating_sub(value) } else { total.saturating_add(value) };
+        }
+        DiceRoll { total, dice }
+    }
+
+    /// Smallest possible total.
+    pub fn min(&self) -> i64 {
+        self.bounds().0
+    }
+
+    /// Largest possible total.
+    pub fn max(&self) -> i64 {
+        self.bounds().1
+    }
+
+    fn bounds(&self) -> (i64, i64) {
+        let (mut lo, mut hi) = (0i64, 0i64);
+        for (negative, term) in &self.terms {
+            let (tlo, thi) = match *term {
+                DiceTerm::Const(c) => (c, c),
+                DiceTerm::Dice { count, sides, keep } => {
+                    let kept = kept_count(count, keep) as i64;
+                    (kept, kept * sides as i64)
+                }
+            };
+            if *negative {
+                lo -= thi;
+                hi -= tlo;
+            } else {
+                lo += tlo;
+                hi += thi;
+            }
+        }
+        (lo, hi)
+    }
+}
+
+fn kept_count(count: u32, keep: Option<Keep>) -> u32 {
+    match keep {
+        None => count,
+        Some(Keep::Highest(n) | Keep::Lowest(n)) => n.min(count),
+        Some(Keep::DropHighest(n) | Keep::DropLowest(n)) => count.saturating_sub(n),
+    }
+}
+
+fn apply_keep(rolled: &mut [DieRoll], keep: Keep) {
+    // Rank dice by value, ties broken by roll order.
+    let mut order: Vec<usize> = (0..rolled.len()).collect();
+    order.sort_by_key(|&i| (rolled[i].value, i));
+
+    let n = rolled.len();
+    let kept = kept_count(n as u32, Some(keep)) as usize;
+    let keep_low = matches!(keep, Keep::Lowest(_) | Keep::DropHighest(_));
+    for (rank, &i) in order.iter().enumerate() {
+        rolled[i].kept = if keep_low { rank < kept } else { rank >= n - kept };
+    }
+}
+
+impl FromStr for DiceExpr {
+    type Err = WMMSCoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for DiceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (negative, term)) in self.terms.iter().enumerate() {
+            if *negative {
+                f.write_str("-")?;
+            } else if i > 0 {
+                f.write_str("+")?;
+            }
+            match term {
+                DiceTerm::Const(c) => write!(f, "{c}")?,
+                DiceTerm::Dice { count, sides, keep } => {
+                    write!(f, "{count}d{sides}")?;
+                    match keep {
+                        Some(Keep::Highest(n)) => write!(f, "kh{n}")?,
+                        Some(Keep::Lowest(n)) => write!(f, "kl{n}")?,
+                        Some(Keep::DropHighest(n)) => write!(f, "dh{n}")?,
+                        Some(Keep::DropLowest(n)) => write!(f, "dl{n}")?,
+                        None => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DetRng {
+    /// Parses and rolls a dice expression in one go.
+    pub fn roll_dice(&mut self, notation: &str) -> Result<DiceRoll> {
+        Ok(DiceExpr::parse(notation)?.roll(self))
+    }
+}
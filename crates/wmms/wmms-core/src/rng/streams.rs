@@ -0,0 +1,122 @@
+//! Named RNG streams derived from a session seed.
+//!
+//! Each system or entity draws from its own stream so that adding a roll in
+//! one place never shifts the rolls seen elsewhere. Streams are keyed by a
+//! stable tag and derived with `DetRng::derive`, so creation order does not
+//! matter either.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::canon::CanonMap;
+use crate::error::{Result, WMMSCoreError};
+use crate::ids::EntityInstId;
+
+use super::{DetRng, DetRngState};
+
+#[derive(Clone, Debug)]
+pub struct RngStreams {
+    session_seed: u64,
+    streams: CanonMap<String, DetRng>,
+}
+
+/// Position of every stream at a commit, in canonical tag order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RngStreamsSnapshot {
+    pub session_seed: u64,
+    pub streams: Vec<(String, DetRngState)>,
+}
+
+impl RngStreams {
+    pub fn new(session_seed: u64) -> Self {
+        Self { session_seed, streams: CanonMap::new() }
+    }
+
+    pub fn session_seed(&self) -> u64 {
+        self.session_seed
+    }
+
+    /// Stream for `tag`, created on first use.
+    pub fn stream(&mut self, tag: &str) -> &mut DetRng {
+        let seed = self.session_seed;
+        self.streams
+            .entry(tag.to_string())
+            .or_insert_with(|| DetRng::derive(seed, tag))
+    }
+
+    /// Per-entity stream, tagged `entity:<id>`.
+    pub fn entity_stream(&mut self, id: EntityInstId) -> &mut DetRng {
+        self.stream(&format!("entity:{id}"))
+    }
+
+    pub fn contains(&self, tag: &str) -> bool {
+        self.streams.contains_key(tag)
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.streams.keys().map(String::as_str)
+    }
+
+    pub fn snapshot(&self) -> RngStreamsSnapshot {
+        RngStreamsSnapshot {
+            session_seed: self.session_seed,
+            streams: self.streams.iter().map(|(tag, rng)| (tag.clone(), rng.state())).collect(),
+        }
+    }
+
+    /// Rebuilds every stream at the exact position captured in `snapshot`.
+    pub fn restore(snapshot: &RngStreamsSnapshot) -> Self {
+        Self {
+            session_seed: snapshot.session_seed,
+            streams: snapshot
+                .streams
+                .iter()
+                .map(|(tag, state)| (tag.clone(), DetRng::from_state(state)))
+                .collect(),
+        }
+    }
+}
+
+impl RngStreamsSnapshot {
+    /// Fixed little-endian encoding: seed, stream count, then `(tag len, tag, state)` per stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.session_seed.to_le_bytes());
+        out.extend_from_slice(&(self.streams.len() as u32).to_le_bytes());
+        for (tag, state) in &self.streams {
+            out.extend_from_slice(&(tag.len() as u32).to_le_bytes());
+            out.extend_from_slice(tag.as_bytes());
+            out.extend_from_slice(&state.to_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut cursor = bytes;
+        let mut take = |n: usize| -> Result<&[u8]> {
+            if cursor.len() < n {
+                return Err(WMMSCoreError::InvalidValue("truncated rng snapshot".into()));
+            }
+            let (head, tail) = cursor.split_at(n);
+            cursor = tail;
+            Ok(head)
+        };
+
+        let session_seed = u64::from_le_bytes(take(8)?.try_into().expect("checked length"));
+        let count = u32::from_le_bytes(take(4)?.try_into().expect("checked length"));
+        let mut streams = Vec::new();
+        for _ in 0..count {
+            let len = u32::from_le_bytes(take(4)?.try_into().expect("checked length")) as usize;
+            let tag = core::str::from_utf8(take(len)?)
+                .map_err(|_| WMMSCoreError::InvalidValue("rng stream tag is not utf-8".into()))?
+                .to_string();
+            let state = DetRngState::from_bytes(take(DetRngState::ENCODED_LEN)?)?;
+            streams.push((tag, state));
+        }
+        if !cursor.is_empty() {
+            return Err(WMMSCoreError::InvalidValue("trailing bytes after rng snapshot".into()));
+        }
+        Ok(Self { session_seed, streams })
+    }
+}
@@ -1,6 +1,8 @@
 use core::time::Duration;
 use crate::error::{Result, WMMSCoreError};
 
+pub mod calendar;
+
 #[repr(transparent)]
 #[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
 pub struct Tick(pub u64);
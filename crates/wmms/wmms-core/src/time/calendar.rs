@@ -0,0 +1,541 @@
+//! In-world calendars mapped onto `Tick`.
+//!
+//! A calendar anchors `Tick(0)` at the first day of `epoch_year` and walks
+//! forward through years of named months, with optional leap days, a week of
+//! arbitrary length, moons and named eras. Dates format and parse through a
+//! small pattern language so authored histories can be written as
+//! `"3rd of Frostmoon, Year 412 of the Second Age"`.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::error::{Result, WMMSCoreError};
+use crate::num::Q16_16;
+
+use super::{Tick, TickDelta};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonthDef {
+    pub name: String,
+    pub days: u32,
+    /// Extra days this month gets in leap years.
+    pub leap_days: u32,
+}
+
+/// Nested leap cycle in the Gregorian style: every `every` years is a leap
+/// year, except every `skip_every` years, except every `keep_every` years.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LeapRule {
+    #[default]
+    None,
+    Cycle { every: u32, skip_every: Option<u32>, keep_every: Option<u32> },
+}
+
+impl LeapRule {
+    pub const GREGORIAN: LeapRule = LeapRule::Cycle { every: 4, skip_every: Some(100), keep_every: Some(400) };
+
+    pub fn is_leap(&self, year: i64) -> bool {
+        match *self {
+            LeapRule::None => false,
+            LeapRule::Cycle { every, skip_every, keep_every } => {
+                let divides = |p: Option<u32>| p.is_some_and(|p| year.rem_euclid(p as i64) == 0);
+                if divides(keep_every) {
+                    return true;
+                }
+                if divides(skip_every) {
+                    return false;
+                }
+                divides(Some(every))
+            }
+        }
+    }
+
+    /// Number of years after which the leap pattern repeats.
+    fn cycle_years(&self) -> u32 {
+        match *self {
+            LeapRule::None => 1,
+            LeapRule::Cycle { every, skip_every, keep_every } => keep_every.or(skip_every).unwrap_or(every),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoonDef {
+    pub name: String,
+    pub period: TickDelta,
+    /// Age of the moon at `Tick(0)`.
+    pub offset: TickDelta,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EraDef {
+    pub name: String,
+    /// Absolute year in which the era begins (its year 1).
+    pub start_year: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CalendarDate {
+    /// Absolute year, counted from the calendar's reckoning.
+    pub year: i64,
+    /// Zero-based month index.
+    pub month: u32,
+    /// One-based day of month.
+    pub day: u32,
+    pub tick_of_day: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoonPhase {
+    pub age: TickDelta,
+    /// Position in the cycle, in `[0, 1)`; 0 is new, 0.5 is full.
+    pub fraction: Q16_16,
+    pub name: &'static str,
+}
+
+const PHASE_NAMES: [&str; 8] = [
+    "new", "waxing crescent", "first quarter", "waxing gibbous",
+    "full", "waning gibbous", "last quarter", "waning crescent",
+];
+
+pub struct CalendarBuilder {
+    name: String,
+    ticks_per_day: u64,
+    epoch_year: i64,
+    epoch_weekday: u32,
+    months: Vec<MonthDef>,
+    weekdays: Vec<String>,
+    leap: LeapRule,
+    moons: Vec<MoonDef>,
+    eras: Vec<EraDef>,
+}
+
+impl CalendarBuilder {
+    pub fn new(name: &str, ticks_per_day: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            ticks_per_day,
+            epoch_year: 1,
+            epoch_weekday: 0,
+            months: Vec::new(),
+            weekdays: Vec::new(),
+            leap: LeapRule::None,
+            moons: Vec::new(),
+            eras: Vec::new(),
+        }
+    }
+
+    /// Absolute year of `Tick(0)` and the weekday index it falls on.
+    pub fn epoch(mut self, year: i64, weekday: u32) -> Self {
+        self.epoch_year = year;
+        self.epoch_weekday = weekday;
+        self
+    }
+
+    pub fn month(mut self, name: &str, days: u32) -> Self {
+        self.months.push(MonthDef { name: name.to_string(), days, leap_days: 0 });
+        self
+    }
+
+    pub fn leap_month(mut self, name: &str, days: u32, leap_days: u32) -> Self {
+        self.months.push(MonthDef { name: name.to_string(), days, leap_days });
+        self
+    }
+
+    pub fn weekdays(mut self, names: &[&str]) -> Self {
+        self.weekdays = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    pub fn leap_rule(mut self, rule: LeapRule) -> Self {
+        self.leap = rule;
+        self
+    }
+
+    pub fn moon(mut self, name: &str, period: TickDelta, offset: TickDelta) -> Self {
+        self.moons.push(MoonDef { name: name.to_string(), period, offset });
+        self
+    }
+
+    pub fn era(mut self, name: &str, start_year: i64) -> Self {
+        self.eras.push(EraDef { name: name.to_string(), start_year });
+        self
+    }
+
+    pub fn build(mut self) -> Result<Calendar> {
+        let invalid = |why: String| WMMSCoreError::InvalidValue(format!("calendar '{}': {why}", self.name));
+        if self.ticks_per_day == 0 {
+            return Err(invalid("ticks_per_day must be positive".into()));
+        }
+        if self.months.is_empty() {
+            return Err(invalid("at least one month is required".into()));
+        }
+        if let Some(m) = self.months.iter().find(|m| m.days == 0) {
+            return Err(invalid(format!("month '{}' has no days", m.name)));
+        }
+        for (i, m) in self.months.iter().enumerate() {
+            if self.months[..i].iter().any(|o| o.name.eq_ignore_ascii_case(&m.name)) {
+                return Err(invalid(format!("duplicate month '{}'", m.name)));
+            }
+        }
+        if let LeapRule::Cycle { every, skip_every, keep_every } = self.leap {
+            let nests = every > 0
+                && skip_every.is_none_or(|s| s > every && s % every == 0)
+                && keep_every.is_none_or(|k| skip_every.is_some_and(|s| k > s && k % s == 0));
+            if !nests {
+                return Err(invalid("leap periods must nest (every | skip_every | keep_every)".into()));
+            }
+        }
+        if self.moons.iter().any(|m| m.period.0 == 0) {
+            return Err(invalid("moon period must be positive".into()));
+        }
+        if !self.weekdays.is_empty() && self.epoch_weekday as usize >= self.weekdays.len() {
+            return Err(invalid("epoch weekday out of range".into()));
+        }
+        self.eras.sort_by_key(|e| e.start_year);
+
+        let mut cal = Calendar {
+            name: self.name,
+            ticks_per_day: self.ticks_per_day,
+            epoch_year: self.epoch_year,
+            epoch_weekday: self.epoch_weekday,
+            months: self.months,
+            weekdays: self.weekdays,
+            leap: self.leap,
+            moons: self.moons,
+            eras: self.eras,
+            cycle_years: 0,
+            cycle_days: 0,
+        };
+        cal.cycle_years = cal.leap.cycle_years() as i64;
+        cal.cycle_days = (0..cal.cycle_years).map(|y| cal.days_in_year(cal.epoch_year + y)).sum();
+        Ok(cal)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Calendar {
+    name: String,
+    ticks_per_day: u64,
+    epoch_year: i64,
+    epoch_weekday: u32,
+    months: Vec<MonthDef>,
+    weekdays: Vec<String>,
+    leap: LeapRule,
+    moons: Vec<MoonDef>,
+    eras: Vec<EraDef>,
+    cycle_years: i64,
+    cycle_days: u64,
+}
+
+impl Calendar {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ticks_per_day(&self) -> u64 {
+        self.ticks_per_day
+    }
+
+    pub fn months(&self) -> &[MonthDef] {
+        &self.months
+    }
+
+    pub fn weekdays(&self) -> &[String] {
+        &self.weekdays
+    }
+
+    pub fn moons(&self) -> &[MoonDef] {
+        &self.moons
+    }
+
+    pub fn eras(&self) -> &[EraDef] {
+        &self.eras
+    }
+
+    pub fn is_leap_year(&self, year: i64) -> bool {
+        self.leap.is_leap(year)
+    }
+
+    pub fn days_in_month(&self, year: i64, month: u32) -> Option<u32> {
+        let m = self.months.get(month as usize)?;
+        Some(if self.is_leap_year(year) { m.days + m.leap_days } else { m.days })
+    }
+
+    pub fn days_in_year(&self, year: i64) -> u64 {
+        let leap = self.is_leap_year(year);
+        self.months
+            .iter()
+            .map(|m| (m.days + if leap { m.leap_days } else { 0 }) as u64)
+            .sum()
+    }
+
+    pub fn date_of(&self, tick: Tick) -> CalendarDate {
+        let tick_of_day = tick.0 % self.ticks_per_day;
+        let mut days = tick.0 / self.ticks_per_day;
+
+        // Skip whole leap cycles, then walk the remaining years and months.
+        let cycles = days / self.cycle_days;
+        days %= self.cycle_days;
+        let mut year = self.epoch_year + cycles as i64 * self.cycle_years;
+        loop {
+            let len = self.days_in_year(year);
+            if days < len {
+                break;
+            }
+            days -= len;
+            year += 1;
+        }
+
+        let mut month = 0u32;
+        loop {
+            let len = self.days_in_month(year, month).expect("month in range") as u64;
+            if days < len {
+                break;
+            }
+            days -= len;
+            month += 1;
+        }
+
+        CalendarDate { year, month, day: days as u32 + 1, tick_of_day }
+    }
+
+    pub fn tick_of(&self, date: &CalendarDate) -> Result<Tick> {
+        let bad = || WMMSCoreError::InvalidDate(format!("{date:?} does not exist in calendar '{}'", self.name));
+        if date.year < self.epoch_year || date.tick_of_day >= self.ticks_per_day {
+            return Err(bad());
+        }
+        let month_len = self.days_in_month(date.year, date.month).ok_or_else(bad)?;
+        if date.day == 0 || date.day > month_len {
+            return Err(bad());
+        }
+
+        let elapsed_years = date.year - self.epoch_year;
+        let cycles = elapsed_years / self.cycle_years;
+        let mut days = cycles as u64 * self.cycle_days;
+        for y in (self.epoch_year + cycles * self.cycle_years)..date.year {
+            days += self.days_in_year(y);
+        }
+        for m in 0..date.month {
+            days += self.days_in_month(date.year, m).expect("month in range") as u64;
+        }
+        days += (date.day - 1) as u64;
+
+        days.checked_mul(self.ticks_per_day)
+            .and_then(|t| t.checked_add(date.tick_of_day))
+            .map(Tick)
+            .ok_or(WMMSCoreError::TickOverflow)
+    }
+
+    pub fn weekday_of(&self, tick: Tick) -> Option<&str> {
+        if self.weekdays.is_empty() {
+            return None;
+        }
+        let days = tick.0 / self.ticks_per_day;
+        let idx = (days + self.epoch_weekday as u64) % self.weekdays.len() as u64;
+        Some(&self.weekdays[idx as usize])
+    }
+
+    /// Era containing `year` and the year number within it.
+    pub fn era_of(&self, year: i64) -> Option<(&EraDef, i64)> {
+        self.eras
+            .iter()
+            .rev()
+            .find(|e| e.start_year <= year)
+            .map(|e| (e, year - e.start_year + 1))
+    }
+
+    pub fn moon_phase(&self, moon: usize, tick: Tick) -> Option<MoonPhase> {
+        let m = self.moons.get(moon)?;
+        let period = m.period.0;
+        let age = ((tick.0 % period) + (m.offset.0 % period)) % period;
+        let fraction = Q16_16::from_raw((((age as u128) << 16) / period as u128) as i32);
+        let idx = ((age as u128 * 8 + period as u128 / 2) / period as u128) % 8;
+        Some(MoonPhase { age: TickDelta(age), fraction, name: PHASE_NAMES[idx as usize] })
+    }
+
+    // ----- formatting and parsing -----
+
+    /// Formats a tick with a pattern. Placeholders:
+    /// `{day}`, `{day_ord}`, `{month}`, `{month_num}`, `{year}`, `{era}`,
+    /// `{era_year}`, `{weekday}`. Use `{{` and `}}` for literal braces.
+    pub fn format(&self, tick: Tick, pattern: &str) -> Result<String> {
+        let date = self.date_of(tick);
+        let mut out = String::new();
+        for piece in tokenize(pattern)? {
+            match piece {
+                Piece::Lit(s) => out.push_str(&s),
+                Piece::Field(f) => match f {
+                    Field::Day => out.push_str(&date.day.to_string()),
+                    Field::DayOrd => out.push_str(&ordinal(date.day)),
+                    Field::Month => out.push_str(&self.months[date.month as usize].name),
+                    Field::MonthNum => out.push_str(&(date.month + 1).to_string()),
+                    Field::Year => out.push_str(&date.year.to_string()),
+                    Field::Era | Field::EraYear => {
+                        let (era, era_year) = self.era_of(date.year).ok_or_else(|| {
+                            WMMSCoreError::InvalidDate(format!("year {} precedes every era", date.year))
+                        })?;
+                        if f == Field::Era {
+                            out.push_str(&era.name);
+                        } else {
+                            out.push_str(&era_year.to_string());
+                        }
+                    }
+                    Field::Weekday => out.push_str(self.weekday_of(tick).unwrap_or("")),
+                },
+            }
+        }
+        Ok(out)
+    }
+
+    /// Parses text written with `pattern` back to the tick at the start of that day.
+    pub fn parse(&self, input: &str, pattern: &str) -> Result<Tick> {
+        let bad = |why: &str| WMMSCoreError::InvalidDate(format!("'{input}' does not match '{pattern}': {why}"));
+        let pieces = tokenize(pattern)?;
+
+        let mut rest = input.trim();
+        let mut day = None;
+        let mut month = None;
+        let mut year = None;
+        let mut era = None;
+        let mut era_year = None;
+        let mut weekday = None;
+
+        for (i, piece) in pieces.iter().enumerate() {
+            match piece {
+                Piece::Lit(lit) => {
+                    rest = rest.strip_prefix(lit.as_str()).ok_or_else(|| bad(&format!("expected '{lit}'")))?;
+                }
+                Piece::Field(f) => {
+                    let end = match pieces.get(i + 1) {
+                        Some(Piece::Lit(next)) => rest.find(next.as_str()).ok_or_else(|| bad(&format!("expected '{next}'")))?,
+                        Some(Piece::Field(_)) => return Err(bad("adjacent fields are ambiguous")),
+                        None => rest.len(),
+                    };
+                    let (value, tail) = rest.split_at(end);
+                    rest = tail;
+                    let number = |s: &str| s.trim().parse::<i64>().map_err(|_| bad(&format!("'{s}' is not a number")));
+                    match f {
+                        Field::Day => day = Some(number(value)?),
+                        Field::DayOrd => {
+                            let digits = value.trim().trim_end_matches(|c: char| c.is_ascii_alphabetic());
+                            day = Some(number(digits)?);
+                        }
+                        Field::Month => {
+                            let idx = self.months.iter().position(|m| m.name.eq_ignore_ascii_case(value.trim()))
+                                .ok_or_else(|| bad(&format!("unknown month '{value}'")))?;
+                            month = Some(idx as u32);
+                        }
+                        Field::MonthNum => {
+                            let n = number(value)?;
+                            if n < 1 {
+                                return Err(bad("month numbers start at 1"));
+                            }
+                            month = Some((n - 1) as u32);
+                        }
+                        Field::Year => year = Some(number(value)?),
+                        Field::Era => {
+                            let e = self.eras.iter().find(|e| e.name.eq_ignore_ascii_case(value.trim()))
+                                .ok_or_else(|| bad(&format!("unknown era '{value}'")))?;
+                            era = Some(e.start_year);
+                        }
+                        Field::EraYear => era_year = Some(number(value)?),
+                        Field::Weekday => weekday = Some(value.trim().to_string()),
+                    }
+                }
+            }
+        }
+        if !rest.trim().is_empty() {
+            return Err(bad("trailing text"));
+        }
+
+        let year = match (year, era, era_year) {
+            (Some(y), _, _) => y,
+            (None, Some(start), Some(n)) => start + n - 1,
+            _ => return Err(bad("no year given")),
+        };
+        let date = CalendarDate {
+            year,
+            month: month.ok_or_else(|| bad("no month given"))?,
+            day: u32::try_from(day.ok_or_else(|| bad("no day given"))?).map_err(|_| bad("day out of range"))?,
+            tick_of_day: 0,
+        };
+        let tick = self.tick_of(&date)?;
+        if let Some(w) = weekday
+            && !self.weekday_of(tick).is_some_and(|d| d.eq_ignore_ascii_case(&w))
+        {
+            return Err(bad(&format!("that day is not a {w}")));
+        }
+        Ok(tick)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Day,
+    DayOrd,
+    Month,
+    MonthNum,
+    Year,
+    Era,
+    EraYear,
+    Weekday,
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Lit(String),
+    Field(Field),
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    let mut lit = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                lit.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                lit.push('}');
+            }
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let field = match name.as_str() {
+                    "day" => Field::Day,
+                    "day_ord" => Field::DayOrd,
+                    "month" => Field::Month,
+                    "month_num" => Field::MonthNum,
+                    "year" => Field::Year,
+                    "era" => Field::Era,
+                    "era_year" => Field::EraYear,
+                    "weekday" => Field::Weekday,
+                    other => return Err(WMMSCoreError::InvalidValue(format!("unknown date field '{{{other}}}'"))),
+                };
+                if !lit.is_empty() {
+                    pieces.push(Piece::Lit(core::mem::take(&mut lit)));
+                }
+                pieces.push(Piece::Field(field));
+            }
+            c => lit.push(c),
+        }
+    }
+    if !lit.is_empty() {
+        pieces.push(Piece::Lit(lit));
+    }
+    Ok(pieces)
+}
+
+fn ordinal(n: u32) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
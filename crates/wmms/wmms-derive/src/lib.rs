@@ -0,0 +1,108 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, parse_macro_input, parse_quote};
+
+/// Derives `wmms_core::hash::StableHash`.
+///
+/// Fields are hashed in declaration order; enums first hash the variant
+/// index as a `u32`. Fields marked `#[stable_hash(skip)]` (caches, dirty
+/// flags) are left out of the digest.
+#[proc_macro_derive(StableHash, attributes(stable_hash))]
+pub fn derive_stable_hash(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::wmms_core::hash::StableHash));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, stmts) = destructure(&data.fields);
+            quote! {
+                let #name #pattern = self;
+                #(#stmts)*
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().enumerate().map(|(idx, v)| {
+                let ident = &v.ident;
+                let idx = idx as u32;
+                let (pattern, stmts) = destructure(&v.fields);
+                quote! {
+                    #name::#ident #pattern => {
+                        ::wmms_core::hash::StableHasher::write_u32(hasher, #idx);
+                        #(#stmts)*
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(&input.ident, "StableHash cannot be derived for unions")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::wmms_core::hash::StableHash for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn stable_hash(&self, hasher: &mut ::wmms_core::hash::StableHasher) {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+fn is_skipped(field: &syn::Field) -> bool {
+    field.attrs.iter().any(|attr| {
+        attr.path().is_ident("stable_hash")
+            && attr
+                .parse_args::<syn::Ident>()
+                .map(|i| i == "skip")
+                .unwrap_or(false)
+    })
+}
+
+/// Binding pattern for the fields plus one hashing statement per kept field.
+fn destructure(fields: &Fields) -> (TokenStream2, Vec<TokenStream2>) {
+    match fields {
+        Fields::Named(named) => {
+            let mut binds = Vec::new();
+            let mut stmts = Vec::new();
+            for f in &named.named {
+                let ident = f.ident.as_ref().expect("named field");
+                if is_skipped(f) {
+                    binds.push(quote!(#ident: _));
+                } else {
+                    binds.push(quote!(#ident));
+                    stmts.push(quote!(::wmms_core::hash::StableHash::stable_hash(#ident, hasher);));
+                }
+            }
+            (quote!({ #(#binds),* }), stmts)
+        }
+        Fields::Unnamed(unnamed) => {
+            let mut binds = Vec::new();
+            let mut stmts = Vec::new();
+            for (i, f) in unnamed.unnamed.iter().enumerate() {
+                if is_skipped(f) {
+                    binds.push(quote!(_));
+                } else {
+                    let ident = format_ident!("f{}", i);
+                    binds.push(quote!(#ident));
+                    stmts.push(quote!(::wmms_core::hash::StableHash::stable_hash(#ident, hasher);));
+                }
+            }
+            (quote!(( #(#binds),* )), stmts)
+        }
+        Fields::Unit => (TokenStream2::new(), Vec::new()),
+    }
+}
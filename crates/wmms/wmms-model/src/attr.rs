@@ -1,14 +1,22 @@
-use wmms_core::{ids::{AbilityId, ArchetypeId, EffectId, EffectInstId, TraitId}, num::Q16_16, time::Tick};
+use std::cmp::Ordering;
 
-use wmms_core::ids::EntityId;
+use wmms_aspects::registry::AspectRid;
+use wmms_core::{canon::CanonMap, ids::{AbilityId, ArchetypeId, EffectId, EffectInstId, TraitId}, num::{Decimal, FixedI64, FixedU32, Q16_16, Q32_32, RoundingMode}, time::{Tick, TickDelta}};
 
+use wmms_core::hash::StableHash;
+use wmms_core::ids::{EntityId, EntityRid};
 
-#[derive(Clone,PartialEq,Debug)]
+use crate::formula::FormulaInput;
+
+
+#[derive(Clone,PartialEq,Debug,StableHash)]
 pub enum AttrValue{
     Null,
     Bool(bool),
     Int(i64),
     Fixed(Q16_16),
+    Wide(Q32_32),
+    Decimal(Decimal),
     Float(f32),
     Str(String),
     Entity(EntityId),
@@ -17,9 +25,169 @@ pub enum AttrValue{
     Ability(AbilityId),
     Effect(EffectId),
     Archtetype(ArchetypeId),    
+
+    Aspect(AspectRid),
+    Tick(Tick),
+    TickDelta(TickDelta),
+    Vec2([Q16_16; 2]),
+    Vec3([Q16_16; 3]),
+    /// Inclusive numeric range.
+    Range { min: Q32_32, max: Q32_32 },
+
+    List(Vec<AttrValue>),
+    /// Distinct values in canonical order; build with [`AttrValue::set`].
+    Set(Vec<AttrValue>),
+    Map(CanonMap<String, AttrValue>),
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+impl AttrValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self { AttrValue::Bool(v) => Some(v), _ => None }
+    }
+    pub fn as_int(&self) -> Option<i64> {
+        match *self { AttrValue::Int(v) => Some(v), _ => None }
+    }
+    pub fn as_fixed(&self) -> Option<Q16_16> {
+        match *self { AttrValue::Fixed(v) => Some(v), _ => None }
+    }
+    pub fn as_wide(&self) -> Option<Q32_32> {
+        match *self { AttrValue::Wide(v) => Some(v), _ => None }
+    }
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        match *self { AttrValue::Decimal(v) => Some(v), _ => None }
+    }
+    pub fn as_float(&self) -> Option<f32> {
+        match *self { AttrValue::Float(v) => Some(v), _ => None }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self { AttrValue::Str(v) => Some(v), _ => None }
+    }
+    pub fn as_entity(&self) -> Option<EntityId> {
+        match *self { AttrValue::Entity(v) => Some(v), _ => None }
+    }
+    pub fn as_trait(&self) -> Option<TraitId> {
+        match *self { AttrValue::Trait(v) => Some(v), _ => None }
+    }
+    pub fn as_ability(&self) -> Option<AbilityId> {
+        match *self { AttrValue::Ability(v) => Some(v), _ => None }
+    }
+    pub fn as_effect(&self) -> Option<EffectId> {
+        match *self { AttrValue::Effect(v) => Some(v), _ => None }
+    }
+    pub fn as_archetype(&self) -> Option<ArchetypeId> {
+        match *self { AttrValue::Archtetype(v) => Some(v), _ => None }
+    }
+    pub fn as_aspect(&self) -> Option<AspectRid> {
+        match *self { AttrValue::Aspect(v) => Some(v), _ => None }
+    }
+    pub fn as_tick(&self) -> Option<Tick> {
+        match *self { AttrValue::Tick(v) => Some(v), _ => None }
+    }
+    pub fn as_tick_delta(&self) -> Option<TickDelta> {
+        match *self { AttrValue::TickDelta(v) => Some(v), _ => None }
+    }
+    pub fn as_vec2(&self) -> Option<[Q16_16; 2]> {
+        match *self { AttrValue::Vec2(v) => Some(v), _ => None }
+    }
+    pub fn as_vec3(&self) -> Option<[Q16_16; 3]> {
+        match *self { AttrValue::Vec3(v) => Some(v), _ => None }
+    }
+    pub fn as_range(&self) -> Option<(Q32_32, Q32_32)> {
+        match *self { AttrValue::Range { min, max } => Some((min, max)), _ => None }
+    }
+    pub fn as_list(&self) -> Option<&[AttrValue]> {
+        match self { AttrValue::List(v) => Some(v), _ => None }
+    }
+    pub fn as_set(&self) -> Option<&[AttrValue]> {
+        match self { AttrValue::Set(v) => Some(v), _ => None }
+    }
+    pub fn as_map(&self) -> Option<&CanonMap<String, AttrValue>> {
+        match self { AttrValue::Map(v) => Some(v), _ => None }
+    }
+
+    /// A set of `values`, sorted and deduplicated by [`AttrValue::canonical_cmp`].
+    pub fn set(values: impl IntoIterator<Item = AttrValue>) -> Self {
+        let mut values: Vec<AttrValue> = values.into_iter().collect();
+        values.sort_by(AttrValue::canonical_cmp);
+        values.dedup_by(|a, b| a.canonical_cmp(b) == Ordering::Equal);
+        AttrValue::Set(values)
+    }
+
+    /// Total order used for set elements: by variant, then by value, with
+    /// floats compared by `total_cmp`.
+    pub fn canonical_cmp(&self, other: &AttrValue) -> Ordering {
+        use AttrValue::*;
+        let by_items = |a: &[AttrValue], b: &[AttrValue]| {
+            a.iter().zip(b).map(|(x, y)| x.canonical_cmp(y)).find(|o| o.is_ne()).unwrap_or(a.len().cmp(&b.len()))
+        };
+        match (self, other) {
+            (Null, Null) => Ordering::Equal,
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Int(a), Int(b)) => a.cmp(b),
+            (Fixed(a), Fixed(b)) => a.cmp(b),
+            (Wide(a), Wide(b)) => a.cmp(b),
+            (Decimal(a), Decimal(b)) => a.cmp(b),
+            (Float(a), Float(b)) => a.total_cmp(b),
+            (Str(a), Str(b)) => a.cmp(b),
+            (Entity(a), Entity(b)) => a.cmp(b),
+            (Trait(a), Trait(b)) => a.cmp(b),
+            (Ability(a), Ability(b)) => a.cmp(b),
+            (Effect(a), Effect(b)) => a.cmp(b),
+            (Archtetype(a), Archtetype(b)) => a.cmp(b),
+            (Aspect(a), Aspect(b)) => a.cmp(b),
+            (Tick(a), Tick(b)) => a.cmp(b),
+            (TickDelta(a), TickDelta(b)) => a.cmp(b),
+            (Vec2(a), Vec2(b)) => a.cmp(b),
+            (Vec3(a), Vec3(b)) => a.cmp(b),
+            (Range { min: a0, max: a1 }, Range { min: b0, max: b1 }) => (a0, a1).cmp(&(b0, b1)),
+            (List(a), List(b)) | (Set(a), Set(b)) => by_items(a, b),
+            (Map(a), Map(b)) => a
+                .iter()
+                .zip(b)
+                .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| va.canonical_cmp(vb)))
+                .find(|o| o.is_ne())
+                .unwrap_or(a.len().cmp(&b.len())),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            AttrValue::Null => 0,
+            AttrValue::Bool(_) => 1,
+            AttrValue::Int(_) => 2,
+            AttrValue::Fixed(_) => 3,
+            AttrValue::Wide(_) => 4,
+            AttrValue::Decimal(_) => 5,
+            AttrValue::Float(_) => 6,
+            AttrValue::Str(_) => 7,
+            AttrValue::Entity(_) => 8,
+            AttrValue::Trait(_) => 9,
+            AttrValue::Ability(_) => 10,
+            AttrValue::Effect(_) => 11,
+            AttrValue::Archtetype(_) => 12,
+            AttrValue::Aspect(_) => 13,
+            AttrValue::Tick(_) => 14,
+            AttrValue::TickDelta(_) => 15,
+            AttrValue::Vec2(_) => 16,
+            AttrValue::Vec3(_) => 17,
+            AttrValue::Range { .. } => 18,
+            AttrValue::List(_) => 19,
+            AttrValue::Set(_) => 20,
+            AttrValue::Map(_) => 21,
+        }
+    }
+
+    /// The elements of a list or set, or the value itself.
+    pub(crate) fn elements(&self) -> &[AttrValue] {
+        match self {
+            AttrValue::List(v) | AttrValue::Set(v) => v,
+            v => std::slice::from_ref(v),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, StableHash)]
 pub enum LayerKind {
     Archetype = 0,
     Trait     = 1,
@@ -27,26 +195,63 @@ pub enum LayerKind {
     Override  = 3,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+/// How a layer combines with the value resolved so far.
+///
+/// Resolution runs in a fixed order: for each `LayerKind` from archetype to
+/// override, `Base` layers (the strongest one wins), then `Add`, then `Mul`;
+/// after all kinds, `ClampMin` then `ClampMax`; last, the strongest `Final`
+/// layer replaces the result outright. Arithmetic between `Int`s saturates
+/// in `i64`; with a fractional operand it runs in `Q32_32`, saturating on
+/// overflow, and converts back to the type of the value it modifies.
+/// Arithmetic on non-numeric values is skipped.
+///
+/// Collections are built per kind after the arithmetic: `Append` then
+/// `Remove`, so several traits can each contribute to a list or set and an
+/// effect can take an element back out.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, StableHash)]
+pub enum ModifierOp {
+    #[default]
+    Base,
+    Add,
+    Mul,
+    /// Raises the result to at least the layer value.
+    ClampMin,
+    /// Lowers the result to at most the layer value.
+    ClampMax,
+    /// Final override, applied after every other layer.
+    Final,
+    /// Adds to a list, set or map: the elements of a list or set layer, the
+    /// entries of a map layer, or the layer value itself. Without a base it
+    /// starts from the layer's collection, or a one-element list.
+    Append,
+    /// Removes from a list or set every element equal to the layer value (or
+    /// to any element of a list or set layer); from a map, the keys named by
+    /// a string, a list or set of strings, or another map.
+    Remove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, StableHash)]
 pub enum LayerSource {
     Archetype(ArchetypeId),
     Trait(TraitId),
     EffectInstance(EffectInstId),
     Override(u64),
     System(u64),
-    
+    /// The formula of a derived attribute.
+    Derived,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, StableHash)]
 pub struct LayerStamp {
     pub tick: Tick,
     pub seq: u32,
 }
 
-#[derive(Clone,Debug, PartialEq)]
+#[derive(Clone,Debug, PartialEq, StableHash)]
 pub struct AttrLayer {
     pub kind: LayerKind,
     pub source: LayerSource,
+    pub op: ModifierOp,
     pub value: AttrValue,
     pub stamp: LayerStamp,
     pub expires_at: Option<Tick>,
@@ -58,12 +263,173 @@ impl AttrLayer {
     fn order_key(&self) -> (LayerKind, i16, LayerSource, Tick, u32) {
         (self.kind, self.priority, self.source, self.stamp.tick, self.stamp.seq)
     }
+
+    /// Position in the resolution order described on [`ModifierOp`].
+    #[inline]
+    fn phase(&self) -> (u8, u8) {
+        match self.op {
+            ModifierOp::Base => (self.kind as u8, 0),
+            ModifierOp::Add => (self.kind as u8, 1),
+            ModifierOp::Mul => (self.kind as u8, 2),
+            ModifierOp::Append => (self.kind as u8, 3),
+            ModifierOp::Remove => (self.kind as u8, 4),
+            ModifierOp::ClampMin => (LayerKind::Override as u8 + 1, 0),
+            ModifierOp::ClampMax => (LayerKind::Override as u8 + 1, 1),
+            ModifierOp::Final => (LayerKind::Override as u8 + 2, 0),
+        }
+    }
+}
+
+/// One step of an attribute resolution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerStep {
+    pub layer: AttrLayer,
+    pub before: Option<AttrValue>,
+    pub after: Option<AttrValue>,
+}
+
+impl LayerStep {
+    /// False when the layer left the value unchanged, e.g. arithmetic on a
+    /// non-numeric value.
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// Every layer of an attribute in resolution order, with the value before
+/// and after it, and the final value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrExplain {
+    pub steps: Vec<LayerStep>,
+    /// The resolved value, clamped to the schema range when there is one.
+    pub value: Option<AttrValue>,
+    /// Formula inputs of a derived attribute, with their current values.
+    pub inputs: Vec<AttrInput>,
+}
+
+/// One input read by a derived attribute's formula.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrInput {
+    pub input: FormulaInput,
+    /// The entity read from, or `None` if the link did not resolve.
+    pub entity: Option<EntityRid>,
+    pub value: Option<AttrValue>,
+}
+
+/// Numeric view of a value in the resolution accumulator type.
+pub(crate) fn to_q32(v: &AttrValue) -> Option<Q32_32> {
+    let saturated = |neg: bool| FixedI64(if neg { i64::MIN } else { i64::MAX });
+    match *v {
+        AttrValue::Int(i) => Some(Q32_32::from_i64(i).unwrap_or(saturated(i < 0))),
+        AttrValue::Fixed(q) => Q32_32::from_fixed32(q).ok(),
+        AttrValue::Wide(q) => Some(q),
+        AttrValue::Decimal(d) => Some(d.to_fixed64(RoundingMode::HalfEven).unwrap_or(saturated(d.is_negative()))),
+        AttrValue::Float(f) => Q32_32::from_f64_quantized(f as f64).ok(),
+        _ => None,
+    }
+}
+
+/// Orders two numeric values, exactly when both are `Int` and in `Q32_32`
+/// otherwise.
+pub(crate) fn cmp_numeric(a: &AttrValue, b: &AttrValue) -> Option<Ordering> {
+    match (a, b) {
+        (AttrValue::Int(a), AttrValue::Int(b)) => Some(a.cmp(b)),
+        _ => Some(to_q32(a)?.cmp(&to_q32(b)?)),
+    }
 }
 
-#[derive(Default,Clone,Debug)]
+/// Converts back to the variant of `like`, saturating where it is narrower.
+pub(crate) fn from_q32(q: Q32_32, like: &AttrValue) -> AttrValue {
+    match like {
+        AttrValue::Int(_) => AttrValue::Int(q.to_i64_round()),
+        AttrValue::Fixed(_) => AttrValue::Fixed(
+            q.to_fixed32().unwrap_or(FixedU32(if q.is_negative() { i32::MIN } else { i32::MAX })),
+        ),
+        AttrValue::Decimal(d) => AttrValue::Decimal(
+            Decimal::from_fixed64(q, d.scale(), RoundingMode::HalfEven).unwrap_or(*d),
+        ),
+        AttrValue::Float(_) => AttrValue::Float(q.to_f64() as f32),
+        _ => AttrValue::Wide(q),
+    }
+}
+
+fn apply_op(op: ModifierOp, acc: Option<&AttrValue>, value: &AttrValue) -> Option<AttrValue> {
+    let max = FixedI64(i64::MAX);
+    let min = FixedI64(i64::MIN);
+    match op {
+        ModifierOp::Base | ModifierOp::Final => Some(value.clone()),
+        ModifierOp::Append => Some(append(acc, value)),
+        ModifierOp::Remove => acc.map(|acc| remove(acc, value)),
+        ModifierOp::ClampMin | ModifierOp::ClampMax if acc.is_none() => None,
+        _ if matches!((acc, value), (None | Some(AttrValue::Int(_)), AttrValue::Int(_))) => {
+            let (a, b) = (acc.and_then(AttrValue::as_int).unwrap_or(0), value.as_int()?);
+            Some(AttrValue::Int(match op {
+                ModifierOp::Add => a.saturating_add(b),
+                ModifierOp::Mul => a.saturating_mul(b),
+                ModifierOp::ClampMin => a.max(b),
+                ModifierOp::ClampMax => a.min(b),
+                ModifierOp::Base | ModifierOp::Final | ModifierOp::Append | ModifierOp::Remove => unreachable!(),
+            }))
+        }
+        _ => {
+            // Without a base, arithmetic starts from zero in the layer's own type.
+            let (Some(a), Some(b)) = (acc.map_or(Some(FixedI64(0)), to_q32), to_q32(value)) else {
+                return acc.cloned();
+            };
+            let out = match op {
+                ModifierOp::Add => a.checked_add(b).unwrap_or(if b.is_negative() { min } else { max }),
+                ModifierOp::Mul => a.checked_mul(b).unwrap_or(if a.is_negative() != b.is_negative() { min } else { max }),
+                ModifierOp::ClampMin => a.max(b),
+                ModifierOp::ClampMax => a.min(b),
+                ModifierOp::Base | ModifierOp::Final | ModifierOp::Append | ModifierOp::Remove => unreachable!(),
+            };
+            Some(from_q32(out, acc.unwrap_or(value)))
+        }
+    }
+}
+
+fn append(acc: Option<&AttrValue>, value: &AttrValue) -> AttrValue {
+    match (acc, value) {
+        (None, AttrValue::List(_) | AttrValue::Set(_) | AttrValue::Map(_)) => value.clone(),
+        (None, v) => AttrValue::List(vec![v.clone()]),
+        (Some(AttrValue::List(list)), v) => AttrValue::List(list.iter().chain(v.elements()).cloned().collect()),
+        (Some(AttrValue::Set(set)), v) => AttrValue::set(set.iter().chain(v.elements()).cloned()),
+        (Some(AttrValue::Map(map)), AttrValue::Map(entries)) => {
+            let mut map = map.clone();
+            map.extend(entries.iter().map(|(k, v)| (k.clone(), v.clone())));
+            AttrValue::Map(map)
+        }
+        (Some(acc), _) => acc.clone(),
+    }
+}
+
+fn remove(acc: &AttrValue, value: &AttrValue) -> AttrValue {
+    let gone = |v: &AttrValue| value.elements().iter().any(|r| r.canonical_cmp(v).is_eq());
+    match acc {
+        AttrValue::List(list) => AttrValue::List(list.iter().filter(|v| !gone(v)).cloned().collect()),
+        AttrValue::Set(set) => AttrValue::Set(set.iter().filter(|v| !gone(v)).cloned().collect()),
+        AttrValue::Map(map) => {
+            let mut map = map.clone();
+            match value {
+                AttrValue::Map(keys) => map.retain(|k, _| !keys.contains_key(k)),
+                keys => {
+                    for key in keys.elements().iter().filter_map(AttrValue::as_str) {
+                        map.remove(key);
+                    }
+                }
+            }
+            AttrValue::Map(map)
+        }
+        acc => acc.clone(),
+    }
+}
+
+#[derive(Default,Clone,Debug,StableHash)]
 pub struct AttrStack {
     layers: Vec<AttrLayer>,
+    #[stable_hash(skip)]
     dirty: bool,
+    #[stable_hash(skip)]
     cached: Option<AttrValue>,
 
 }
@@ -88,7 +454,7 @@ impl AttrStack {
 
     // Ensure layers are sorted by priority and stamp
     fn sort_layers(&mut self) {
-        self.layers.sort_by(|a,b| a.order_key().cmp(&b.order_key()));
+        self.layers.sort_by_key(|l| l.order_key());
 
         let mut i = 0usize;
         while i + 1 < self.layers.len() {
@@ -103,10 +469,10 @@ impl AttrStack {
                     self.layers.remove(i + 1);
                 }
                 continue; // re-check at same index
-                }
             }
             i += 1;
         }
+    }
 
     // Upsert a layer based on its source
     pub fn upsert(&mut self, layer: AttrLayer) {
@@ -142,17 +508,42 @@ impl AttrStack {
         }
     }
 
-    /// Resolve cached winner if dirty. Winner = last layer (strongest).
+    /// Resolves the cached value if dirty, see [`ModifierOp`] for the order.
     pub fn resolve(&mut self) -> Option<&AttrValue> {
+        self.resolve_with(|v| v)
+    }
+
+    /// Like [`resolve`](Self::resolve), passing a freshly resolved value
+    /// through `bound`, e.g. to clamp it to its schema range.
+    pub fn resolve_with(&mut self, bound: impl FnOnce(AttrValue) -> AttrValue) -> Option<&AttrValue> {
         if self.dirty {
-           self.cached = self.layers.last().map(|l| l.value.clone());
+           self.cached = self.run(|_, _, _| {}).map(bound);
            self.dirty = false;
         }
         self.cached.as_ref()
     }
 
-    pub fn explain(&self) -> &[AttrLayer] {
-        &self.layers
+    /// Resolves from scratch, recording each layer's contribution.
+    pub fn explain(&self) -> AttrExplain {
+        let mut steps = Vec::with_capacity(self.layers.len());
+        let value = self.run(|layer, before, after| {
+            steps.push(LayerStep { layer: layer.clone(), before: before.cloned(), after: after.cloned() });
+        });
+        AttrExplain { steps, value, inputs: Vec::new() }
+    }
+
+    fn run(&self, mut step: impl FnMut(&AttrLayer, Option<&AttrValue>, Option<&AttrValue>)) -> Option<AttrValue> {
+        // Layers are in strength order; a stable sort keeps it within each phase.
+        let mut order: Vec<&AttrLayer> = self.layers.iter().collect();
+        order.sort_by_key(|l| l.phase());
+
+        let mut acc: Option<AttrValue> = None;
+        for layer in order {
+            let next = apply_op(layer.op, acc.as_ref(), &layer.value);
+            step(layer, acc.as_ref(), next.as_ref());
+            acc = next;
+        }
+        acc
     }
 
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use wmms_core::ids::{AttrKeyId, TraitId};
+use wmms_core::ids::{AttrKeyId, RelationId, TraitId};
 
 use wmms_core::{ids::EffectInstId, ids::EntityRid};
 
@@ -16,7 +16,11 @@ pub struct ModelDiff {
     pub effect_removed: Vec<EffectInstId>,
 
     pub aspects_changed: Vec<EntityRid>,
-    
+
+    /// Edges as stored, `(source, relation, target)`; re-adding an edge to
+    /// change its attributes also lands in `relation_added`.
+    pub relation_added: Vec<(EntityRid, RelationId, EntityRid)>,
+    pub relation_removed: Vec<(EntityRid, RelationId, EntityRid)>,
 }
 impl ModelDiff {
 
@@ -34,5 +38,7 @@ impl ModelDiff {
         Self::sort_dedup(&mut self.effect_added);
         Self::sort_dedup(&mut self.effect_removed);
         Self::sort_dedup(&mut self.aspects_changed);
+        Self::sort_dedup(&mut self.relation_added);
+        Self::sort_dedup(&mut self.relation_removed);
     }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
-use wmms_core::{ids::{EffectId, EffectInstId, EntityRid}, time::Tick};
+use wmms_core::{hash::StableHash, ids::{EffectId, EffectInstId, EntityRid}, time::Tick};
 
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,StableHash)]
 pub struct EffectInstance {
     pub inst_id: EffectInstId,
     pub effect_id: EffectId,
@@ -1,13 +1,16 @@
 
+use std::collections::BTreeSet;
+
 use wmms_aspects::registry::AspectRid;
-use wmms_core::{ids::{AttrKeyId, EffectId, EffectInstId, EntityRid, TraitId}, time::Tick};
+use wmms_core::{ids::{AttrKeyId, EffectId, EffectInstId, EntityRid, RelationId, TraitId}, time::Tick};
 
-use crate::{attr::{AttrLayer, AttrValue, LayerKind, LayerSource, LayerStamp}, effect::EffectInstance, model::Model};
+use crate::{attr::{AttrLayer, AttrValue, LayerKind, LayerSource, LayerStamp, ModifierOp}, effect::EffectInstance, error::{ModelResult, RelationError}, model::Model, relations::EdgeAttrs};
 
 #[derive(Clone, Debug)]
 pub struct AttrLayerSpec {
     pub kind: LayerKind,
     pub source: LayerSource,
+    pub op: ModifierOp,
     pub value: AttrValue,
     pub expires_at: Option<Tick>,
     pub priority: i16,
@@ -19,6 +22,7 @@ impl AttrLayerSpec {
         AttrLayer {
             kind: self.kind,
             source: self.source,
+            op: self.op,
             value: self.value,
             stamp: LayerStamp { tick, seq },
             expires_at: self.expires_at,
@@ -66,6 +70,21 @@ pub enum EffectOp {
         aspects: Vec<AspectRid>,
     },
 
+    // ----- Relations -----
+    /// Adds `from relation to`, or replaces the edge's attributes.
+    Relate {
+        from: EntityRid,
+        relation: RelationId,
+        to: EntityRid,
+        attrs: EdgeAttrs,
+    },
+
+    Unrelate {
+        from: EntityRid,
+        relation: RelationId,
+        to: EntityRid,
+    },
+
     // ----- Effects (Instances) -----
     ApplyEffect {
         spec: EffectSpec,
@@ -96,7 +115,22 @@ impl ApplyCtx {
     }
 }
 
-pub fn apply_ops(model: &mut Model, ctx: &mut ApplyCtx, ops: &[EffectOp]) {
+/// Applies `ops` in order.
+///
+/// Aspect, attribute and relation ops are validated first, so a batch
+/// breaking an exclusive aspect group, an attribute schema or a relation's
+/// cardinality, or relating a dead entity, is rejected before anything is
+/// applied.
+pub fn apply_ops(model: &mut Model, ctx: &mut ApplyCtx, ops: &[EffectOp]) -> ModelResult<()> {
+    for op in ops {
+        match op {
+            EffectOp::SetAspectsDirect { aspects, .. } => model.aspects_reg.check_exclusive(aspects)?,
+            EffectOp::UpsertAttrLayer { key, layer, .. } => model.check_attr_layer(*key, layer.op, &layer.value)?,
+            _ => {}
+        }
+    }
+    check_relation_ops(model, ops)?;
+
     for op in ops {
         match op {
             EffectOp::AddTrait { target, trait_id } => {
@@ -107,17 +141,23 @@ pub fn apply_ops(model: &mut Model, ctx: &mut ApplyCtx, ops: &[EffectOp]) {
             }
             EffectOp::UpsertAttrLayer { target, key, layer } => {
                 let layer = layer.clone().into_layer(ctx.now, ctx.next_seq());
-                model.upsert_attr_layer(*target, *key, layer);
+                model.upsert_attr_layer(*target, *key, layer)?;
             }
             EffectOp::RemoveAttrLayersBySource { target, source } => {
                 if let Some(ent) = model.entity_mut(*target){
-                    for (_k, stack) in ent.attrs.stacks.iter_mut(){
+                    for stack in ent.attrs.stacks.values_mut(){
                         stack.remove_by_source(*source);
                     }
                 }
             }
+            EffectOp::Relate { from, relation, to, attrs } => {
+                model.relate(*from, *relation, *to, attrs.clone())?;
+            }
+            EffectOp::Unrelate { from, relation, to } => {
+                model.unrelate(*from, *relation, *to)?;
+            }
             EffectOp::SetAspectsDirect { target, aspects } => {
-                model.set_entity_aspects(*target, aspects.as_slice());
+                model.set_entity_aspects(*target, aspects.as_slice())?;
             }
             EffectOp::ApplyEffect { spec } => {
                let inst_id = model.alloc_effect_inst();
@@ -140,4 +180,46 @@ pub fn apply_ops(model: &mut Model, ctx: &mut ApplyCtx, ops: &[EffectOp]) {
             }
         }
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+/// Replays the relation and kill ops of a batch, in order, on a copy of the
+/// edges they touch, so cardinality and liveness hold for the batch as a
+/// whole before any op runs.
+fn check_relation_ops(model: &Model, ops: &[EffectOp]) -> Result<(), RelationError> {
+    let mut touched = BTreeSet::new();
+    for op in ops {
+        if let EffectOp::Relate { from, to, .. } | EffectOp::Unrelate { from, to, .. } = op {
+            touched.extend([*from, *to]);
+        }
+    }
+    if touched.is_empty() {
+        return Ok(());
+    }
+
+    let defs = model.relation_defs();
+    let mut scratch = model.relations_touching(&touched);
+    let mut killed = BTreeSet::new();
+    for op in ops {
+        match op {
+            EffectOp::KillEntity { target } => {
+                killed.insert(*target);
+                scratch.remove_entity(*target);
+            }
+            EffectOp::Relate { from, relation, to, .. } => {
+                let view = defs.view_or_err(*relation)?;
+                for rid in [*from, *to] {
+                    if killed.contains(&rid) || !model.is_alive(rid) {
+                        return Err(RelationError::DeadEntity { relation: view.def.name.clone(), entity: rid });
+                    }
+                }
+                scratch.insert(view, *from, *to, EdgeAttrs::new())?;
+            }
+            EffectOp::Unrelate { from, relation, to } => {
+                scratch.remove(defs.view_or_err(*relation)?, *from, *to);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -1,10 +1,11 @@
 use std::collections::BTreeMap;
 use wmms_aspects::set::AspectSet;
+use wmms_core::hash::StableHash;
 use wmms_core::ids::{ArchetypeId, AttrKeyId, EffectInstId, EntityId, EntityInstId, EntityRid, TraitId};
 
 use crate::{attr::{AttrStack}};
 
-#[derive(Debug)]
+#[derive(Debug,StableHash)]
 pub struct EntityRecord {
     pub id: EntityId,
     pub rid: EntityRid,
@@ -18,7 +19,7 @@ pub struct EntityRecord {
     pub attrs: EntityAttrs,
 }
 
-#[derive(Default,Debug)]
+#[derive(Default,Debug,StableHash)]
 pub struct EntityAttrs {
     pub(crate) stacks: BTreeMap<AttrKeyId, AttrStack>,
 }
@@ -0,0 +1,97 @@
+use wmms_aspects::error::AspectError;
+use wmms_core::ids::EntityRid;
+
+use crate::schema::AttrType;
+
+pub type ModelResult<T> = Result<T, ModelError>;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ModelError {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Aspect(#[from] AspectError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Attr(#[from] AttrError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Relation(#[from] RelationError),
+}
+
+/// A value that does not fit its attribute schema.
+#[derive(Debug, Clone, PartialEq, thiserror::Error, miette::Diagnostic)]
+pub enum AttrError {
+    #[error("attribute {0} has no schema")]
+    Unknown(String),
+
+    #[error("attribute '{attr}' expects {expected}, got {found}")]
+    TypeMismatch {
+        attr: String,
+        expected: AttrType,
+        found: String,
+    },
+
+    #[error("attribute '{attr}' holds {expected} elements, got {found}")]
+    ElementMismatch {
+        attr: String,
+        expected: AttrType,
+        found: String,
+    },
+
+    #[error("attribute '{attr}' value {value} is outside {range}")]
+    OutOfRange {
+        attr: String,
+        value: String,
+        range: String,
+    },
+
+    #[error("attribute '{attr}' value {value} is not one of {domain}")]
+    NotInDomain {
+        attr: String,
+        value: String,
+        domain: String,
+    },
+
+    #[error("duplicate attribute schema '{0}'")]
+    Duplicate(String),
+
+    #[error("derived attribute '{0}' must have a numeric type")]
+    DerivedNotNumeric(String),
+
+    #[error("derived attributes form a cycle: {0}")]
+    Cycle(String),
+}
+
+/// A relation declaration or edge the relation graph rejects.
+#[derive(Debug, Clone, PartialEq, thiserror::Error, miette::Diagnostic)]
+pub enum RelationError {
+    #[error("relation {0} is not declared")]
+    Unknown(String),
+
+    #[error("duplicate relation '{0}'")]
+    Duplicate(String),
+
+    #[error("symmetric relation '{0}' cannot be one-to-many or have an inverse")]
+    Asymmetric(String),
+
+    #[error("relation '{relation}' allows one {side} per entity and {entity} already has one")]
+    Cardinality {
+        relation: String,
+        side: &'static str,
+        entity: EntityRid,
+    },
+
+    #[error("relation '{relation}' needs live entities, {entity} is not")]
+    DeadEntity {
+        relation: String,
+        entity: EntityRid,
+    },
+
+    #[error("relation '{relation}' cannot link {entity} to itself")]
+    SelfEdge {
+        relation: String,
+        entity: EntityRid,
+    },
+}
@@ -0,0 +1,136 @@
+use std::ops;
+
+use wmms_core::{ids::AttrKeyId, num::{FixedI64, Q32_32}};
+
+/// Where a formula reads a value from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FormulaInput {
+    /// Entity-valued attribute to follow first, or `None` for the entity itself.
+    pub link: Option<AttrKeyId>,
+    pub key: AttrKeyId,
+}
+
+/// Deterministic arithmetic over attributes, evaluated in `Q32_32`.
+///
+/// Missing or non-numeric inputs read as zero, overflow saturates and
+/// division by zero gives zero, so every formula yields a value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Formula {
+    Const(Q32_32),
+    /// An attribute of the same entity.
+    Attr(AttrKeyId),
+    /// `key` of the entity referenced by this entity's `link` attribute.
+    Linked { link: AttrKeyId, key: AttrKeyId },
+    Add(Box<Formula>, Box<Formula>),
+    Sub(Box<Formula>, Box<Formula>),
+    Mul(Box<Formula>, Box<Formula>),
+    Div(Box<Formula>, Box<Formula>),
+    Min(Box<Formula>, Box<Formula>),
+    Max(Box<Formula>, Box<Formula>),
+}
+
+impl Formula {
+    pub fn attr(key: AttrKeyId) -> Self {
+        Formula::Attr(key)
+    }
+
+    pub fn linked(link: AttrKeyId, key: AttrKeyId) -> Self {
+        Formula::Linked { link, key }
+    }
+
+    pub fn int(v: i64) -> Self {
+        Formula::Const(Q32_32::from_i64(v).unwrap_or(saturated(v < 0)))
+    }
+
+    pub fn min(self, other: Formula) -> Self {
+        Formula::Min(Box::new(self), Box::new(other))
+    }
+
+    pub fn max(self, other: Formula) -> Self {
+        Formula::Max(Box::new(self), Box::new(other))
+    }
+
+    /// Distinct inputs in canonical order.
+    pub fn inputs(&self) -> Vec<FormulaInput> {
+        let mut out = Vec::new();
+        self.collect_inputs(&mut out);
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    fn collect_inputs(&self, out: &mut Vec<FormulaInput>) {
+        match self {
+            Formula::Const(_) => {}
+            Formula::Attr(key) => out.push(FormulaInput { link: None, key: *key }),
+            Formula::Linked { link, key } => out.push(FormulaInput { link: Some(*link), key: *key }),
+            Formula::Add(a, b)
+            | Formula::Sub(a, b)
+            | Formula::Mul(a, b)
+            | Formula::Div(a, b)
+            | Formula::Min(a, b)
+            | Formula::Max(a, b) => {
+                a.collect_inputs(out);
+                b.collect_inputs(out);
+            }
+        }
+    }
+
+    /// Evaluates with `read` supplying the numeric value of each input.
+    pub fn eval(&self, read: &impl Fn(&FormulaInput) -> Option<Q32_32>) -> Q32_32 {
+        let zero = FixedI64(0);
+        match self {
+            Formula::Const(q) => *q,
+            Formula::Attr(key) => read(&FormulaInput { link: None, key: *key }).unwrap_or(zero),
+            Formula::Linked { link, key } => read(&FormulaInput { link: Some(*link), key: *key }).unwrap_or(zero),
+            Formula::Add(a, b) => {
+                let (a, b) = (a.eval(read), b.eval(read));
+                a.checked_add(b).unwrap_or(saturated(b.is_negative()))
+            }
+            Formula::Sub(a, b) => {
+                let (a, b) = (a.eval(read), b.eval(read));
+                a.checked_sub(b).unwrap_or(saturated(!b.is_negative()))
+            }
+            Formula::Mul(a, b) => {
+                let (a, b) = (a.eval(read), b.eval(read));
+                a.checked_mul(b).unwrap_or(saturated(a.is_negative() != b.is_negative()))
+            }
+            Formula::Div(a, b) => {
+                let (a, b) = (a.eval(read), b.eval(read));
+                if b == zero {
+                    return zero;
+                }
+                a.checked_div(b).unwrap_or(saturated(a.is_negative() != b.is_negative()))
+            }
+            Formula::Min(a, b) => a.eval(read).min(b.eval(read)),
+            Formula::Max(a, b) => a.eval(read).max(b.eval(read)),
+        }
+    }
+}
+
+fn saturated(neg: bool) -> Q32_32 {
+    FixedI64(if neg { i64::MIN } else { i64::MAX })
+}
+
+macro_rules! formula_op {
+    ($trait:ident, $method:ident, $variant:ident) => {
+        impl ops::$trait for Formula {
+            type Output = Formula;
+            fn $method(self, rhs: Formula) -> Formula {
+                Formula::$variant(Box::new(self), Box::new(rhs))
+            }
+        }
+
+        impl ops::$trait<i64> for Formula {
+            type Output = Formula;
+            fn $method(self, rhs: i64) -> Formula {
+                Formula::$variant(Box::new(self), Box::new(Formula::int(rhs)))
+            }
+        }
+    };
+}
+
+formula_op!(Add, add, Add);
+formula_op!(Sub, sub, Sub);
+formula_op!(Mul, mul, Mul);
+formula_op!(Div, div, Div);
@@ -1,4 +1,5 @@
 use roaring::RoaringBitmap;
+use wmms_aspects::expr::AspectExpr;
 use wmms_aspects::registry::AspectRid;
 
 use wmms_core::ids::EntityRid;
@@ -35,4 +36,39 @@ impl AspectIndex {
     pub fn bitmap(&self, aspect: AspectRid) -> Option<&RoaringBitmap> {
         self.by_aspect.get(aspect.0 as usize)
     }
-}
\ No newline at end of file
+
+    /// Number of entities holding each aspect, indexed by RID.
+    pub fn usage_counts(&self) -> Vec<u64> {
+        self.by_aspect.iter().map(|b| b.len()).collect()
+    }
+
+    /// Entities of `universe` matching `expr`.
+    ///
+    /// `Not` complements against `universe`, so pass the set of live entities.
+    pub fn eval(&self, expr: &AspectExpr, universe: &RoaringBitmap) -> RoaringBitmap {
+        match expr {
+            AspectExpr::True => universe.clone(),
+            AspectExpr::False => RoaringBitmap::new(),
+            AspectExpr::Has(rid) => self.bitmap(*rid).map(|b| b & universe).unwrap_or_default(),
+            AspectExpr::Not(inner) => universe - self.eval(inner, universe),
+            AspectExpr::And(terms) => {
+                // Each operand only needs evaluating inside what is still matched.
+                let mut acc = universe.clone();
+                for term in terms {
+                    if acc.is_empty() {
+                        break;
+                    }
+                    acc = self.eval(term, &acc);
+                }
+                acc
+            }
+            AspectExpr::Or(terms) => {
+                let mut acc = RoaringBitmap::new();
+                for term in terms {
+                    acc |= self.eval(term, universe);
+                }
+                acc
+            }
+        }
+    }
+}
@@ -2,9 +2,416 @@ pub mod aspects;
 pub mod attr;
 pub mod diff;
 pub mod entity;
+pub mod error;
+pub mod formula;
 pub mod index;
 pub mod relations;
+pub mod schema;
 pub mod view;
 pub mod effect;
 pub mod model;
-pub mod effect_ops;
\ No newline at end of file
+pub mod effect_ops;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use wmms_aspects::{expr::AspectExpr, registry::AspectRegistryBuilder};
+    use wmms_core::ids::{EntityAuthId, EntityId};
+
+    use crate::{
+        attr::{AttrLayer, AttrValue, LayerKind, LayerSource, LayerStamp, ModifierOp},
+        model::Model,
+    };
+
+    fn empty_model() -> Model {
+        Model::new(Arc::new(AspectRegistryBuilder::new().seal().unwrap()))
+    }
+
+    fn layer(kind: LayerKind, source: LayerSource, op: ModifierOp, value: AttrValue) -> AttrLayer {
+        let stamp = LayerStamp { tick: wmms_core::time::Tick(0), seq: 0 };
+        AttrLayer { kind, source, op, value, stamp, expires_at: None, priority: 0 }
+    }
+
+    #[test]
+    fn aspect_expressions_evaluate_on_the_index() {
+        let mut b = AspectRegistryBuilder::new();
+        for p in ["class.mage", "class.warrior", "state.silenced", "trait.scroll_user"] {
+            b.register(p).unwrap();
+        }
+        let reg = Arc::new(b.seal().unwrap());
+        let rid = |p: &str| reg.resolve_path(p).unwrap();
+
+        let mut m = Model::new(reg.clone());
+        let mut spawn = |name: &str, aspects: &[&str]| {
+            let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new(name)));
+            m.set_entity_aspects(e, &aspects.iter().map(|p| rid(p)).collect::<Vec<_>>()).unwrap();
+            e
+        };
+        let mage = spawn("mage", &["class.mage"]);
+        let _silenced = spawn("silenced", &["class.mage", "state.silenced"]);
+        let scribe = spawn("scribe", &["class.warrior", "state.silenced", "trait.scroll_user"]);
+        let _warrior = spawn("warrior", &["class.warrior"]);
+        let gone = spawn("gone", &["class.mage"]);
+        m.kill_entity(gone);
+
+        let e = AspectExpr::compile("(class.mage and not state.silenced) or trait.scroll_user", &reg).unwrap();
+        let hits: Vec<u32> = m.matching(&e).iter().collect();
+        assert_eq!(hits, vec![mage.as_u32(), scribe.as_u32()]);
+    }
+
+    #[test]
+    fn registry_migration_rebuilds_the_index() {
+        let mut b = AspectRegistryBuilder::new();
+        b.register("class.mage").unwrap();
+        b.register("state.silenced").unwrap();
+        let old = Arc::new(b.seal().unwrap());
+
+        let mut b = AspectRegistryBuilder::new();
+        b.register("class.bard").unwrap();
+        b.rename("class.mage", "class.wizard").unwrap();
+        b.register("state.silenced").unwrap();
+        let new = Arc::new(b.seal().unwrap());
+
+        let mut m = Model::new(old.clone());
+        let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new("mage")));
+        m.set_entity_aspects(e, &[old.resolve_path("class.mage").unwrap(), old.resolve_path("state.silenced").unwrap()]).unwrap();
+        m.take_diff();
+
+        let diff = m.migrate_registry(new.clone());
+        assert_eq!(diff.aliased.len(), 1);
+        assert_eq!(m.take_diff().aspects_changed, vec![e]);
+        let q = AspectExpr::compile("class.wizard and state.silenced", &new).unwrap();
+        assert_eq!(m.matching(&q).iter().collect::<Vec<_>>(), vec![e.as_u32()]);
+        assert!(m.matching(&AspectExpr::compile("class.bard", &new).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn exclusive_groups_are_enforced() {
+        use wmms_aspects::{error::AspectError, meta::AspectMeta};
+        use wmms_core::time::Tick;
+        use crate::{effect_ops::{ApplyCtx, EffectOp, apply_ops}, view::ModelView};
+
+        let mut b = AspectRegistryBuilder::new();
+        for p in ["state.stance.defensive.turtle", "state.stance.aggressive", "state.silenced"] {
+            b.register(p).unwrap();
+        }
+        b.exclusive("state.stance").unwrap();
+        b.describe("state.stance.aggressive", AspectMeta::new().display_name("Aggressive").color("#c0392b")).unwrap();
+        let reg = Arc::new(b.seal().unwrap());
+        let rid = |p: &str| reg.resolve_path(p).unwrap();
+        assert_eq!(reg.meta(rid("state.stance.aggressive")).display_name.as_deref(), Some("Aggressive"));
+        assert_eq!(reg.exclusive_groups().collect::<Vec<_>>(), vec![rid("state.stance")]);
+
+        let mut m = Model::new(reg.clone());
+        let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new("knight")));
+        m.set_entity_aspects(e, &[rid("state.stance.defensive.turtle"), rid("state.stance.defensive"), rid("state.silenced")]).unwrap();
+        let err = m.set_entity_aspects(e, &[rid("state.stance.aggressive"), rid("state.stance.defensive.turtle")]).unwrap_err();
+        assert!(matches!(err, AspectError::Exclusive { ref group, .. } if group == "state.stance"));
+        assert!(m.aspects(e).contains(rid("state.silenced")));
+
+        let mut ctx = ApplyCtx { now: Tick(0), seq: 0 };
+        let ops = [
+            EffectOp::SetAspectsDirect { target: e, aspects: vec![rid("state.stance.aggressive")] },
+            EffectOp::SetAspectsDirect { target: e, aspects: vec![rid("state.stance.aggressive"), rid("state.stance.defensive")] },
+        ];
+        assert!(apply_ops(&mut m, &mut ctx, &ops).is_err());
+        assert!(!m.matching(&AspectExpr::Has(rid("state.stance.aggressive"))).contains(e.as_u32()));
+    }
+
+    #[test]
+    fn attr_modifiers_stack_in_order() {
+        use wmms_core::{ids::{ArchetypeId, AttrKeyId, EffectInstId, TraitId}, num::Q16_16, time::Tick};
+        use crate::view::ModelView;
+
+        let mut m = empty_model();
+        let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new("mage")));
+        let fire = AttrKeyId::new("power.fire");
+        let buff = LayerSource::EffectInstance(EffectInstId::from(1u64));
+        let cap = LayerSource::System(1);
+        let layers = [
+            layer(LayerKind::Effect, buff, ModifierOp::Mul, AttrValue::Fixed(Q16_16::from_f32_quantized(1.5))),
+            layer(LayerKind::Trait, LayerSource::Trait(TraitId::new("pyromancer")), ModifierOp::Add, AttrValue::Int(10)),
+            layer(LayerKind::Archetype, LayerSource::Archetype(ArchetypeId::new("mage")), ModifierOp::Base, AttrValue::Int(20)),
+            layer(LayerKind::Override, cap, ModifierOp::ClampMax, AttrValue::Int(40)),
+        ];
+        for l in layers {
+            m.upsert_attr_layer(e, fire, l).unwrap();
+        }
+        m.finalize_commit(Tick(1));
+        assert_eq!(m.get_attr(e, fire), Some(&AttrValue::Int(40)));
+
+        let explain = m.explain_attr(e, fire).unwrap();
+        let trace: Vec<_> = explain.steps.iter().map(|s| (s.layer.op, s.after.clone())).collect();
+        assert_eq!(trace, vec![
+            (ModifierOp::Base, Some(AttrValue::Int(20))),
+            (ModifierOp::Add, Some(AttrValue::Int(30))),
+            (ModifierOp::Mul, Some(AttrValue::Int(45))),
+            (ModifierOp::ClampMax, Some(AttrValue::Int(40))),
+        ]);
+
+        m.upsert_attr_layer(e, fire, layer(LayerKind::Trait, LayerSource::Override(7), ModifierOp::Final, AttrValue::Int(99))).unwrap();
+        m.finalize_commit(Tick(2));
+        assert_eq!(m.get_attr(e, fire), Some(&AttrValue::Int(99)));
+
+        // Int arithmetic stays in i64, well past the range of Q32_32.
+        let gold = AttrKeyId::new("gold");
+        m.upsert_attr_layer(e, gold, layer(LayerKind::Archetype, cap, ModifierOp::Base, AttrValue::Int(5_000_000_000))).unwrap();
+        m.upsert_attr_layer(e, gold, layer(LayerKind::Trait, cap, ModifierOp::Add, AttrValue::Int(0))).unwrap();
+        m.finalize_commit(Tick(3));
+        assert_eq!(m.get_attr(e, gold), Some(&AttrValue::Int(5_000_000_000)));
+        m.upsert_attr_layer(e, gold, layer(LayerKind::Effect, cap, ModifierOp::Mul, AttrValue::Int(i64::MAX))).unwrap();
+        m.finalize_commit(Tick(4));
+        assert_eq!(m.get_attr(e, gold), Some(&AttrValue::Int(i64::MAX)));
+    }
+
+    #[test]
+    fn attr_schemas_validate_layers() {
+        use wmms_core::{ids::AttrKeyId, num::Q16_16, time::Tick};
+        use crate::{
+            error::AttrError,
+            schema::{AttrSchema, AttrSchemaRegistry, AttrType},
+            view::ModelView,
+        };
+
+        let mut schemas = AttrSchemaRegistry::new();
+        schemas.register(AttrSchema::new("health", AttrType::Int).default_value(AttrValue::Int(100))
+            .range(Some(AttrValue::Int(0)), Some(AttrValue::Int(500)))).unwrap();
+        schemas.register(AttrSchema::new("element", AttrType::Str)
+            .domain([AttrValue::Str("fire".into()), AttrValue::Str("ice".into())])).unwrap();
+        assert!(matches!(schemas.register(AttrSchema::new("health", AttrType::Int)), Err(AttrError::Duplicate(_))));
+
+        let health = AttrKeyId::new("health");
+        let element = AttrKeyId::new("element");
+        assert!(matches!(schemas.schema(health).unwrap().check_value(&AttrValue::Int(900)), Err(AttrError::OutOfRange { .. })));
+        let gold = AttrSchema::new("gold", AttrType::Int).range(None, Some(AttrValue::Int(3_000_000_000)));
+        assert!(gold.check_value(&AttrValue::Int(3_000_000_000)).is_ok());
+        assert!(matches!(gold.check_value(&AttrValue::Int(3_000_000_001)), Err(AttrError::OutOfRange { .. })));
+        assert_eq!(gold.clamp(AttrValue::Int(5_000_000_000)), AttrValue::Int(3_000_000_000));
+
+        let mut m = empty_model().with_attr_schemas(Arc::new(schemas));
+        let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new("golem")));
+        assert_eq!(m.get_int(e, health), Some(100));
+
+        let by_op = |op, value| {
+            let kind = if op == ModifierOp::Base { LayerKind::Archetype } else { LayerKind::Trait };
+            layer(kind, LayerSource::System(1), op, value)
+        };
+        let half = AttrValue::Fixed(Q16_16::from_f32_quantized(0.5));
+        assert!(matches!(m.upsert_attr_layer(e, health, by_op(ModifierOp::Base, half.clone())), Err(AttrError::TypeMismatch { .. })));
+        assert!(matches!(m.upsert_attr_layer(e, element, by_op(ModifierOp::Base, AttrValue::Str("mud".into()))), Err(AttrError::NotInDomain { .. })));
+        assert!(matches!(m.upsert_attr_layer(e, AttrKeyId::new("mana"), by_op(ModifierOp::Base, AttrValue::Int(1))), Err(AttrError::Unknown(_))));
+        assert!(matches!(m.upsert_attr_layer(e, element, by_op(ModifierOp::Add, AttrValue::Int(1))), Err(AttrError::TypeMismatch { .. })));
+
+        m.upsert_attr_layer(e, health, by_op(ModifierOp::Base, AttrValue::Int(80))).unwrap();
+        m.upsert_attr_layer(e, health, by_op(ModifierOp::Mul, half)).unwrap();
+        m.upsert_attr_layer(e, element, by_op(ModifierOp::Base, AttrValue::Str("ice".into()))).unwrap();
+        m.finalize_commit(Tick(1));
+        assert_eq!(m.get_int(e, health), Some(40));
+        assert_eq!(m.get_str(e, element), Some("ice"));
+        assert_eq!(m.get_fixed(e, health), None);
+
+        let boost = |op, value| layer(LayerKind::Effect, LayerSource::System(2), op, value);
+        m.upsert_attr_layer(e, health, boost(ModifierOp::Add, AttrValue::Int(1000))).unwrap();
+        m.finalize_commit(Tick(2));
+        assert_eq!(m.get_int(e, health), Some(500));
+        assert_eq!(m.explain_attr(e, health).unwrap().value, Some(AttrValue::Int(500)));
+        m.upsert_attr_layer(e, health, boost(ModifierOp::Mul, AttrValue::Int(-3))).unwrap();
+        m.finalize_commit(Tick(3));
+        assert_eq!(m.get_int(e, health), Some(0));
+    }
+
+    #[test]
+    fn collection_layers_merge_contributions() {
+        use wmms_core::{ids::{AttrKeyId, TraitId}, time::Tick};
+        use crate::{error::AttrError, schema::{AttrSchema, AttrType}, view::ModelView};
+
+        let mut m = empty_model();
+        let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new("bard")));
+        let languages = AttrKeyId::new("languages");
+        let lang = |s: &str| AttrValue::Str(s.into());
+        let ops = [
+            layer(LayerKind::Archetype, LayerSource::System(1), ModifierOp::Base, AttrValue::set([lang("common")])),
+            layer(LayerKind::Trait, LayerSource::Trait(TraitId::new("elf")), ModifierOp::Append, AttrValue::set([lang("elvish"), lang("common")])),
+            layer(LayerKind::Trait, LayerSource::Trait(TraitId::new("scholar")), ModifierOp::Append, lang("draconic")),
+            layer(LayerKind::Effect, LayerSource::System(2), ModifierOp::Remove, lang("common")),
+        ];
+        for l in ops {
+            m.upsert_attr_layer(e, languages, l).unwrap();
+        }
+        m.finalize_commit(Tick(1));
+        assert_eq!(m.get_attr(e, languages), Some(&AttrValue::set([lang("elvish"), lang("draconic")])));
+
+        let typed = AttrSchema::new("languages", AttrType::Set).elements(AttrType::Str);
+        assert!(typed.check_layer(ModifierOp::Append, &AttrValue::set([lang("dwarvish")])).is_ok());
+        assert!(matches!(typed.check_layer(ModifierOp::Append, &AttrValue::Int(3)), Err(AttrError::ElementMismatch { .. })));
+        assert!(matches!(typed.check_value(&AttrValue::set([lang("orcish"), AttrValue::Int(3)])), Err(AttrError::ElementMismatch { .. })));
+    }
+
+    #[test]
+    fn derived_attrs_follow_their_inputs() {
+        use wmms_core::{ids::AttrKeyId, time::Tick};
+        use crate::{
+            error::AttrError,
+            formula::Formula,
+            schema::{AttrSchema, AttrSchemaRegistry, AttrType},
+            view::ModelView,
+        };
+
+        let key = AttrKeyId::new;
+        let mut schemas = AttrSchemaRegistry::new();
+        for name in ["constitution", "level", "owner"] {
+            let ty = if name == "owner" { AttrType::Entity } else { AttrType::Int };
+            schemas.register(AttrSchema::new(name, ty)).unwrap();
+        }
+        let max_health = Formula::attr(key("constitution")) * 10 + Formula::attr(key("level")) * 5;
+        schemas.register(AttrSchema::new("health.max", AttrType::Int).derived(max_health)).unwrap();
+        schemas.register(AttrSchema::new("pet.bonus", AttrType::Int).derived(Formula::linked(key("owner"), key("health.max")) / 10)).unwrap();
+        schemas.register(AttrSchema::new("a", AttrType::Int).derived(Formula::attr(key("b")))).unwrap();
+        let cycle = schemas.register(AttrSchema::new("b", AttrType::Int).derived(Formula::attr(key("a")) + 1));
+        assert_eq!(cycle, Err(AttrError::Cycle("b -> a -> b".into())));
+
+        let mut m = empty_model().with_attr_schemas(Arc::new(schemas));
+        let hero_id = EntityId::Auth(EntityAuthId::new("hero"));
+        let hero = m.spawn_entity(hero_id);
+        let pet = m.spawn_entity(EntityId::Auth(EntityAuthId::new("wolf")));
+        let set = |m: &mut Model, rid, name, value| {
+            m.upsert_attr_layer(rid, key(name), layer(LayerKind::Archetype, LayerSource::System(1), ModifierOp::Base, value)).unwrap();
+        };
+        set(&mut m, hero, "constitution", AttrValue::Int(12));
+        set(&mut m, hero, "level", AttrValue::Int(3));
+        set(&mut m, pet, "owner", AttrValue::Entity(hero_id));
+        m.finalize_commit(Tick(1));
+        assert_eq!(m.get_int(hero, key("health.max")), Some(135));
+        assert_eq!(m.get_int(pet, key("pet.bonus")), Some(14));
+
+        set(&mut m, hero, "level", AttrValue::Int(5));
+        m.take_diff();
+        m.finalize_commit(Tick(2));
+        assert_eq!(m.get_int(hero, key("health.max")), Some(145));
+        assert_eq!(m.get_int(pet, key("pet.bonus")), Some(15));
+        assert!(m.take_diff().attr_changed.contains(&(pet, key("pet.bonus"))));
+
+        let explain = m.explain_attr(hero, key("health.max")).unwrap();
+        let inputs: Vec<_> = explain.inputs.iter().map(|i| (i.input.key, i.value.clone())).collect();
+        let mut expected = vec![(key("constitution"), Some(AttrValue::Int(12))), (key("level"), Some(AttrValue::Int(5)))];
+        expected.sort_by_key(|(k, _)| *k);
+        assert_eq!(inputs, expected);
+
+        m.kill_entity(hero);
+        m.finalize_commit(Tick(3));
+        assert_eq!(m.get_int(pet, key("pet.bonus")), Some(0));
+
+        let ranger_id = EntityId::Auth(EntityAuthId::new("ranger"));
+        let ranger = m.spawn_entity(ranger_id);
+        set(&mut m, ranger, "constitution", AttrValue::Int(20));
+        set(&mut m, pet, "owner", AttrValue::Entity(ranger_id));
+        m.finalize_commit(Tick(4));
+        assert_eq!(m.get_int(pet, key("pet.bonus")), Some(20));
+        set(&mut m, ranger, "level", AttrValue::Int(2));
+        m.finalize_commit(Tick(5));
+        assert_eq!(m.get_int(pet, key("pet.bonus")), Some(21));
+    }
+
+    #[test]
+    fn derived_attrs_start_from_input_defaults() {
+        use wmms_core::{ids::AttrKeyId, time::Tick};
+        use crate::{formula::Formula, schema::{AttrSchema, AttrSchemaRegistry, AttrType}, view::ModelView};
+
+        let con = AttrKeyId::new("con");
+        let mut schemas = AttrSchemaRegistry::new();
+        schemas.register(AttrSchema::new("con", AttrType::Int).default_value(AttrValue::Int(10))).unwrap();
+        schemas.register(AttrSchema::new("hp.max", AttrType::Int).derived(Formula::attr(con) * 10)).unwrap();
+
+        let mut m = empty_model().with_attr_schemas(Arc::new(schemas));
+        let e = m.spawn_entity(EntityId::Auth(EntityAuthId::new("golem")));
+        m.finalize_commit(Tick(1));
+        assert_eq!(m.get_int(e, AttrKeyId::new("hp.max")), Some(100));
+    }
+
+    #[test]
+    fn relations_are_indexed_both_ways() {
+        use wmms_core::{canon::CanonMap, ids::{AttrKeyId, RelationId}, time::Tick};
+        use crate::{
+            effect_ops::{ApplyCtx, AttrLayerSpec, EffectOp, apply_ops},
+            error::{ModelError, RelationError},
+            relations::{Cardinality, RelationDef, RelationRegistry},
+            view::ModelView,
+        };
+
+        let mut defs = RelationRegistry::new();
+        defs.register(RelationDef::new("parent_of", Cardinality::OneToMany).inverse("child_of")).unwrap();
+        defs.register(RelationDef::new("ally_of", Cardinality::ManyToMany).symmetric()).unwrap();
+        assert!(defs.register(RelationDef::new("child_of", Cardinality::OneToOne)).is_err());
+
+        let mut m = empty_model().with_relations(Arc::new(defs));
+        let [mother, father, kid, friend] = ["mother", "father", "kid", "friend"].map(|n| m.spawn_entity(EntityId::Auth(EntityAuthId::new(n))));
+        let (parent_of, child_of, ally_of) = (RelationId::new("parent_of"), RelationId::new("child_of"), RelationId::new("ally_of"));
+        let since = CanonMap::from([(AttrKeyId::new("since"), AttrValue::Int(3))]);
+        let relate = |from, relation, to, attrs| EffectOp::Relate { from, relation, to, attrs };
+
+        let mut ctx = ApplyCtx { now: Tick(0), seq: 0 };
+        apply_ops(&mut m, &mut ctx, &[
+            relate(mother, parent_of, kid, CanonMap::new()),
+            relate(friend, ally_of, kid, since.clone()),
+        ]).unwrap();
+        assert_eq!(m.related(mother, parent_of), vec![kid]);
+        assert_eq!(m.related(kid, child_of), vec![mother]);
+        assert_eq!(m.related_from(kid, parent_of), vec![mother]);
+        assert_eq!(m.related(kid, ally_of), vec![friend]);
+        assert_eq!(m.relation_attrs(kid, ally_of, friend), Some(&since));
+
+        let second_parent = apply_ops(&mut m, &mut ctx, &[relate(kid, child_of, father, CanonMap::new())]);
+        assert!(matches!(second_parent, Err(ModelError::Relation(RelationError::Cardinality { .. }))));
+        let unknown = apply_ops(&mut m, &mut ctx, &[
+            EffectOp::Unrelate { from: mother, relation: parent_of, to: kid },
+            relate(mother, RelationId::new("rival_of"), kid, CanonMap::new()),
+        ]);
+        assert!(matches!(unknown, Err(ModelError::Relation(RelationError::Unknown(_)))));
+        assert_eq!(m.related(mother, parent_of), vec![kid]);
+
+        // A failing batch leaves the model untouched, whichever op fails.
+        let mood = AttrKeyId::new("mood");
+        let upsert = EffectOp::UpsertAttrLayer {
+            target: kid,
+            key: mood,
+            layer: AttrLayerSpec {
+                kind: LayerKind::Effect, source: LayerSource::System(1), op: ModifierOp::Base,
+                value: AttrValue::Int(1), expires_at: None, priority: 0,
+            },
+        };
+        let before = m.checksum();
+        let batch = apply_ops(&mut m, &mut ctx, &[upsert.clone(), relate(father, parent_of, kid, CanonMap::new())]);
+        assert!(matches!(batch, Err(ModelError::Relation(RelationError::Cardinality { .. }))));
+        let batch = apply_ops(&mut m, &mut ctx, &[
+            upsert.clone(),
+            EffectOp::KillEntity { target: father },
+            relate(father, ally_of, friend, CanonMap::new()),
+        ]);
+        assert!(matches!(batch, Err(ModelError::Relation(RelationError::DeadEntity { .. }))));
+        let batch = apply_ops(&mut m, &mut ctx, &[upsert.clone(), relate(friend, ally_of, friend, CanonMap::new())]);
+        assert!(matches!(batch, Err(ModelError::Relation(RelationError::SelfEdge { .. }))));
+        assert_eq!(m.related(friend, ally_of), vec![kid]);
+        assert_eq!(m.checksum(), before);
+        assert!(m.is_alive(father));
+
+        // Ops are checked in order, so moving an edge within a batch is fine.
+        apply_ops(&mut m, &mut ctx, &[
+            upsert,
+            EffectOp::Unrelate { from: mother, relation: parent_of, to: kid },
+            relate(father, parent_of, kid, CanonMap::new()),
+        ]).unwrap();
+        assert_eq!(m.related(kid, child_of), vec![father]);
+        apply_ops(&mut m, &mut ctx, &[
+            EffectOp::Unrelate { from: father, relation: parent_of, to: kid },
+            relate(mother, parent_of, kid, CanonMap::new()),
+        ]).unwrap();
+
+        m.take_diff();
+        m.kill_entity(kid);
+        assert!(m.related(mother, parent_of).is_empty());
+        assert!(m.related(friend, ally_of).is_empty());
+        assert_eq!(m.take_diff().relation_removed.len(), 2);
+    }
+}
+
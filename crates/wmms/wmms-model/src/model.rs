@@ -1,20 +1,32 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::{BTreeMap, BTreeSet}, sync::Arc};
 
-use wmms_aspects::{registry::{AspectRegistry, AspectRid}, set::AspectSet};
-use wmms_core::{ids::{AttrKeyId, EffectInstId, EntityId, EntityRid, TraitId}, time::Tick};
+use roaring::RoaringBitmap;
+use wmms_aspects::{diff::RegistryDiff, error::AspectResult, expr::AspectExpr, registry::{AspectRegistry, AspectRid}, set::AspectSet};
+use wmms_core::{hash::{Hash128, StableHash, StableHasher}, ids::{AttrKeyId, EffectInstId, EntityId, EntityRid, RelationId, TraitId}, time::Tick};
 
-use crate::{attr::{AttrLayer, AttrStack, AttrValue}, diff::ModelDiff, effect::EffectInstance, entity::EntityRecord, index::AspectIndex, view::ModelView};
+use crate::{attr::{AttrExplain, AttrInput, AttrLayer, AttrStack, AttrValue, LayerKind, LayerSource, LayerStamp, ModifierOp, to_q32}, diff::ModelDiff, error::{AttrError, RelationError}, formula::FormulaInput, relations::{EdgeAttrs, RelationRegistry, RelationStore}, schema::AttrSchemaRegistry, effect::EffectInstance, entity::EntityRecord, index::AspectIndex, view::ModelView};
 
 pub struct Model {
     pub aspects_reg: Arc<AspectRegistry>,
     entities: Vec<EntityRecord>,
     by_id: BTreeMap<EntityId, EntityRid>,
     pub aspect_index: AspectIndex,
+    attr_schemas: Option<Arc<AttrSchemaRegistry>>,
+    relation_defs: Arc<RelationRegistry>,
+    relations: RelationStore,
+    /// Entities by an entity-valued attribute and the entity it resolves
+    /// to, so derived attributes find who links to an entity without a scan.
+    /// Follows resolved values, which only change in `finalize_commit`.
+    links: BTreeMap<(AttrKeyId, EntityId), BTreeSet<EntityRid>>,
 
     effects: Vec<EffectInstance>,
     next_effect_inst: u64,
 
     pending_diff: ModelDiff,
+    /// Entities spawned and killed since the last `finalize_commit`, kept
+    /// apart from `pending_diff` so `take_diff` does not hide them from it.
+    spawned_since_commit: Vec<EntityRid>,
+    killed_since_commit: Vec<EntityRid>,
 }
 
 impl Model {
@@ -25,12 +37,83 @@ impl Model {
             entities: Vec::new(),
             by_id: BTreeMap::new(),
             aspect_index: AspectIndex::new(num_aspects),
+            attr_schemas: None,
+            relation_defs: Arc::default(),
+            relations: RelationStore::default(),
+            links: BTreeMap::new(),
             effects: Vec::new(),
             next_effect_inst: 0,
             pending_diff: ModelDiff::default(),
+            spawned_since_commit: Vec::new(),
+            killed_since_commit: Vec::new(),
         }
     }
 
+    /// Validates attribute layers against `schemas` from now on; unset
+    /// attributes then read as their schema default.
+    pub fn with_attr_schemas(mut self, schemas: Arc<AttrSchemaRegistry>) -> Self {
+        self.attr_schemas = Some(schemas);
+        self
+    }
+
+    pub fn attr_schemas(&self) -> Option<&AttrSchemaRegistry> {
+        self.attr_schemas.as_deref()
+    }
+
+    /// Checks a layer against the attribute's schema. Always passes when the
+    /// model has no schemas.
+    pub fn check_attr_layer(&self, key: AttrKeyId, op: ModifierOp, value: &AttrValue) -> Result<(), AttrError> {
+        match &self.attr_schemas {
+            Some(schemas) => schemas.check_layer(key, op, value),
+            None => Ok(()),
+        }
+    }
+
+    /// Declares the relation types edges may use.
+    pub fn with_relations(mut self, defs: Arc<RelationRegistry>) -> Self {
+        self.relation_defs = defs;
+        self
+    }
+
+    pub fn relation_defs(&self) -> &RelationRegistry {
+        &self.relation_defs
+    }
+
+    /// Copy of the edges touching `rids`, for validating a batch of ops.
+    pub(crate) fn relations_touching(&self, rids: &BTreeSet<EntityRid>) -> RelationStore {
+        self.relations.subset(rids)
+    }
+
+    pub(crate) fn is_alive(&self, rid: EntityRid) -> bool {
+        self.entity(rid).is_some_and(|e| e.alive)
+    }
+
+    /// Adds `from rel to`, or replaces the attributes of that edge.
+    ///
+    /// Only reachable through [`EffectOp::Relate`](crate::effect_ops::EffectOp::Relate)
+    /// so that every edge change goes through an effect.
+    pub(crate) fn relate(&mut self, from: EntityRid, rel: RelationId, to: EntityRid, attrs: EdgeAttrs) -> Result<(), RelationError> {
+        let defs = self.relation_defs.clone();
+        let view = defs.view_or_err(rel)?;
+        for rid in [from, to] {
+            if !self.is_alive(rid) {
+                return Err(RelationError::DeadEntity { relation: view.def.name.clone(), entity: rid });
+            }
+        }
+        let (src, dst) = self.relations.insert(view, from, to, attrs)?;
+        self.pending_diff.relation_added.push((src, view.def.id, dst));
+        Ok(())
+    }
+
+    pub(crate) fn unrelate(&mut self, from: EntityRid, rel: RelationId, to: EntityRid) -> Result<(), RelationError> {
+        let defs = self.relation_defs.clone();
+        let view = defs.view_or_err(rel)?;
+        if let Some((src, dst)) = self.relations.remove(view, from, to) {
+            self.pending_diff.relation_removed.push((src, view.def.id, dst));
+        }
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn entity(&self, rid: EntityRid) -> Option<&EntityRecord> {
         self.entities.get(rid.as_usize())
@@ -63,6 +146,7 @@ impl Model {
         self.by_id.insert(id, rid);
 
         self.pending_diff.spawned.push(rid);
+        self.spawned_since_commit.push(rid);
         rid
     }
 
@@ -90,22 +174,42 @@ impl Model {
             self.aspect_index.remove(rid, a);
         }
 
+        let links: Vec<_> = self
+            .entity(rid)
+            .into_iter()
+            .flat_map(|e| e.attrs.stacks.iter())
+            .filter_map(|(key, stack)| Some((*key, stack.cached()?.as_entity()?)))
+            .collect();
+        for (key, target) in links {
+            self.unlink_attr(rid, key, target);
+        }
+
+        let edges = self.relations.remove_entity(rid);
+        self.pending_diff.relation_removed.extend(edges);
+
         self.pending_diff.killed.push(rid);
+        self.killed_since_commit.push(rid);
 
     }
 
-    pub fn set_entity_aspects(&mut self, rid: EntityRid, direct: &[AspectRid]) {
+    /// Replaces the direct aspects of an entity.
+    ///
+    /// Fails, leaving the entity untouched, if `direct` holds two branches of
+    /// an exclusive group.
+    pub fn set_entity_aspects(&mut self, rid: EntityRid, direct: &[AspectRid]) -> AspectResult<()> {
+        self.aspects_reg.check_exclusive(direct)?;
+
         // Build the new set (direct + all ancestors)
         let new_aspects = self.aspects_reg.close_under_ancestors(direct);
 
         // Take old aspect to avoid overlapping mutable borrows
         let old_aspects = if let Some(entity) = self.entity_mut(rid) {
             if !entity.alive {
-                return;
+                return Ok(());
             }
             core::mem::take(&mut entity.aspects)
         } else {
-            return;
+            return Ok(());
         };
 
         // remove aspects that are no longer apply
@@ -133,6 +237,7 @@ impl Model {
         if changed {
             self.pending_diff.aspects_changed.push(rid);
         }
+        Ok(())
     }
 
     fn insert_sorted_unique_trait(v: &mut Vec<TraitId>, t: TraitId) -> bool {
@@ -177,10 +282,13 @@ impl Model {
         }
     }
 
-    pub fn upsert_attr_layer(&mut self, rid: EntityRid, key: AttrKeyId, layer: AttrLayer) {
-        let Some(entity) = self.entity_mut(rid) else {return;};
+    /// Adds or replaces a layer, rejecting values the attribute's schema does
+    /// not allow.
+    pub fn upsert_attr_layer(&mut self, rid: EntityRid, key: AttrKeyId, layer: AttrLayer) -> Result<(), AttrError> {
+        self.check_attr_layer(key, layer.op, &layer.value)?;
+        let Some(entity) = self.entity_mut(rid) else {return Ok(());};
         if !entity.alive {
-            return;
+            return Ok(());
         }
 
         if let Some(stack) = entity.attrs.stack_mut(&key) {
@@ -192,9 +300,13 @@ impl Model {
         }
 
         self.pending_diff.attr_changed.push((rid, key));
+        Ok(())
     }
 
     pub fn finalize_commit(&mut self, now: Tick) {
+        let mut changed = BTreeSet::new();
+        let mut relinked = Vec::new();
+        let schemas = self.attr_schemas.as_deref();
         for (i, entity) in self.entities.iter_mut().enumerate() {
             if !entity.alive {
                 continue;
@@ -207,10 +319,118 @@ impl Model {
 
                 if stack.is_dirty() {
                     self.pending_diff.attr_changed.push((rid, *key));
-                    let _ = stack.resolve();
+                    changed.insert((rid, *key));
+                    let before = stack.cached().and_then(AttrValue::as_entity);
+                    let after = stack
+                        .resolve_with(|v| match schemas {
+                            Some(schemas) => schemas.clamp(*key, v),
+                            None => v,
+                        })
+                        .and_then(AttrValue::as_entity);
+                    if before != after {
+                        relinked.push((rid, *key, before, after));
+                    }
                 }
             }
         }
+        for (rid, key, before, after) in relinked {
+            if let Some(target) = before {
+                self.unlink_attr(rid, key, target);
+            }
+            if let Some(target) = after {
+                self.links.entry((key, target)).or_default().insert(rid);
+            }
+        }
+        self.recompute_derived(now, changed);
+    }
+
+    /// Recomputes the derived attributes whose inputs are in `changed`, all
+    /// derived attributes of entities spawned since the last commit, and
+    /// those reading a killed entity through a link.
+    ///
+    /// Derived attributes run in dependency order and add what they change
+    /// to `changed`, so one pass reaches every attribute downstream.
+    fn recompute_derived(&mut self, now: Tick, mut changed: BTreeSet<(EntityRid, AttrKeyId)>) {
+        let spawned = std::mem::take(&mut self.spawned_since_commit);
+        let killed = std::mem::take(&mut self.killed_since_commit);
+        let Some(schemas) = self.attr_schemas.clone() else { return };
+        for (schema, formula) in schemas.derived() {
+            let inputs = formula.inputs();
+            let mut targets: BTreeSet<EntityRid> = spawned.iter().copied().collect();
+            for link in inputs.iter().filter_map(|i| i.link) {
+                for &dead in &killed {
+                    targets.extend(self.linking_to(dead, link));
+                }
+            }
+            for &(rid, key) in &changed {
+                for input in &inputs {
+                    match input.link {
+                        None if input.key == key => {
+                            targets.insert(rid);
+                        }
+                        Some(link) => {
+                            if link == key {
+                                targets.insert(rid);
+                            }
+                            if input.key == key {
+                                targets.extend(self.linking_to(rid, link));
+                            }
+                        }
+                        None => {}
+                    }
+                }
+            }
+
+            let values: Vec<(EntityRid, AttrValue)> = targets
+                .into_iter()
+                .map(|rid| {
+                    let q = formula.eval(&|input| self.read_input(rid, input).1.and_then(to_q32));
+                    (rid, schema.derived_value(q))
+                })
+                .collect();
+            for (rid, value) in values {
+                let Some(entity) = self.entity_mut(rid).filter(|e| e.alive) else { continue };
+                let stack = entity.attrs.stacks.entry(schema.key).or_default();
+                let before = stack.cached().cloned();
+                stack.upsert(AttrLayer {
+                    kind: LayerKind::Archetype,
+                    source: LayerSource::Derived,
+                    op: ModifierOp::Base,
+                    value,
+                    stamp: LayerStamp { tick: now, seq: 0 },
+                    expires_at: None,
+                    priority: 0,
+                });
+                if stack.resolve_with(|v| schema.clamp(v)) != before.as_ref() {
+                    self.pending_diff.attr_changed.push((rid, schema.key));
+                    changed.insert((rid, schema.key));
+                }
+            }
+        }
+    }
+
+    /// The entity a formula input reads from, and its value there.
+    fn read_input(&self, rid: EntityRid, input: &FormulaInput) -> (Option<EntityRid>, Option<&AttrValue>) {
+        let entity = match input.link {
+            None => Some(rid),
+            Some(link) => self.get_attr(rid, link).and_then(AttrValue::as_entity).and_then(|id| self.rid_of(id)),
+        };
+        (entity, entity.and_then(|e| self.get_attr(e, input.key)))
+    }
+
+    /// Live entities whose `link` attribute references `target`.
+    fn linking_to(&self, target: EntityRid, link: AttrKeyId) -> Vec<EntityRid> {
+        let Some(id) = self.id_of(target) else { return Vec::new() };
+        self.links.get(&(link, id)).into_iter().flatten().copied().collect()
+    }
+
+    fn unlink_attr(&mut self, rid: EntityRid, key: AttrKeyId, target: EntityId) {
+        if let Some(rids) = self.links.get_mut(&(key, target)) {
+            rids.remove(&rid);
+            if rids.is_empty() {
+                self.links.remove(&(key, target));
+            }
+        }
     }
 
     // Effects instances
@@ -228,15 +448,12 @@ impl Model {
             Ok(pos) => { self.effects[pos] = inst; true},
             Err(pos) => { self.effects.insert(pos, inst); false},
         };
-        if !existed{
-            if let Some(ent) = self.entity_mut(owner) {
-                if ent.alive {
-                    if ent.effects.binary_search(&inst_id).is_err() {
-                        let pos = ent.effects.binary_search(&inst_id).err().unwrap_or_default();
-                        ent.effects.insert(pos, inst_id);
-                    }
-                }
-            }
+        if !existed
+            && let Some(ent) = self.entity_mut(owner)
+            && ent.alive
+            && let Err(pos) = ent.effects.binary_search(&inst_id)
+        {
+            ent.effects.insert(pos, inst_id);
         }
     }
 
@@ -254,15 +471,67 @@ impl Model {
             self.pending_diff.effect_removed.push(inst_id);
         }
 
-        if let Some(owner) = owner {
-            if let Some(ent) = self.entity_mut(owner) {
-                if ent.alive {
-                    if let Ok(pos) = ent.effects.binary_search(&inst_id) {
-                        ent.effects.remove(pos);
-                    }
-                }
+        if let Some(owner) = owner
+            && let Some(ent) = self.entity_mut(owner)
+            && ent.alive
+            && let Ok(pos) = ent.effects.binary_search(&inst_id)
+        {
+            ent.effects.remove(pos);
+        }
+    }
+
+    /// Moves the model onto a new aspect taxonomy.
+    ///
+    /// Every entity's aspects are remapped through the registry diff and the
+    /// index is rebuilt for the new RIDs. Entities whose aspect paths changed
+    /// are reported in the pending diff.
+    pub fn migrate_registry(&mut self, to: Arc<AspectRegistry>) -> RegistryDiff {
+        let diff = self.aspects_reg.diff(&to);
+        let mut index = AspectIndex::new(to.len());
+        for entity in self.entities.iter_mut().filter(|e| e.alive) {
+            let aspects = diff.remap.remap_set(&entity.aspects, &to);
+            let moved = aspects.as_slice().len() != entity.aspects.as_slice().len()
+                || entity.aspects.as_slice().iter().any(|&a| {
+                    diff.remap.get(a).is_none_or(|b| self.aspects_reg.key(a) != to.key(b))
+                });
+            for &a in aspects.as_slice() {
+                index.insert(entity.rid, a);
+            }
+            if moved {
+                self.pending_diff.aspects_changed.push(entity.rid);
             }
+            entity.aspects = aspects;
         }
+        self.aspect_index = index;
+        self.aspects_reg = to;
+        diff
+    }
+
+    /// Live entities matching `expr`, as entity rids.
+    pub fn matching(&self, expr: &AspectExpr) -> RoaringBitmap {
+        let live: RoaringBitmap = self
+            .entities
+            .iter()
+            .filter(|e| e.alive)
+            .map(|e| e.rid.as_u32())
+            .collect();
+        self.aspect_index.eval(expr, &live)
+    }
+
+    /// World checksum for desync detection and replay verification.
+    ///
+    /// Covers the aspect registry, every entity record (dead ones included, since
+    /// rids are positional), effect instances and the effect id counter. Caches
+    /// and the pending diff are left out, so two models that went through the
+    /// same commits agree on it regardless of how or when they were resolved.
+    pub fn checksum(&self) -> Hash128 {
+        let mut h = StableHasher::with_domain("wmms.model.checksum");
+        self.aspects_reg.registry_hash.stable_hash(&mut h);
+        self.entities.stable_hash(&mut h);
+        self.effects.stable_hash(&mut h);
+        self.next_effect_inst.stable_hash(&mut h);
+        self.relations.stable_hash(&mut h);
+        h.finish()
     }
 
     pub fn take_diff(&mut self) -> ModelDiff {
@@ -301,23 +570,56 @@ impl ModelView for Model {
         q.matches(&entity.aspects)
     }
 
+    fn explain_match(&self, rid: EntityRid, q: &wmms_aspects::query::AspectQuery) -> Option<wmms_aspects::query::QueryExplain> {
+        let entity = self.entity(rid).filter(|e| e.alive)?;
+        Some(q.explain(&entity.aspects, &self.aspects_reg))
+    }
+
     fn get_attr(&self, rid: EntityRid, key: AttrKeyId) -> Option<&AttrValue> {
         let entity = self.entity(rid)?;
         if !entity.alive {
             return None;
         }
-        let stack = entity.attrs.stack(&key)?;
-        stack.cached()
+        let value = entity.attrs.stack(&key).and_then(|stack| stack.cached());
+        value.or_else(|| self.attr_schemas.as_deref()?.default_value(key))
     }
 
-    fn explain_attr(&self, rid: EntityRid, key: AttrKeyId) -> Option<&[AttrLayer]> {
+    fn explain_attr(&self, rid: EntityRid, key: AttrKeyId) -> Option<AttrExplain> {
         let entity = self.entity(rid)?;
         if !entity.alive {
             return None;
         }
 
-        let stack = entity.attrs.stack(&key)?;
-        Some(stack.layers())
+        let mut explain = entity.attrs.stack(&key)?.explain();
+        let schema = self.attr_schemas.as_deref().and_then(|s| s.get(key));
+        if let Some(schema) = schema {
+            explain.value = explain.value.map(|v| schema.clamp(v));
+        }
+        if let Some(formula) = schema.and_then(|s| s.formula.as_ref()) {
+            explain.inputs = formula
+                .inputs()
+                .into_iter()
+                .map(|input| {
+                    let (entity, value) = self.read_input(rid, &input);
+                    AttrInput { input, entity, value: value.cloned() }
+                })
+                .collect();
+        }
+        Some(explain)
+    }
+
+    fn related(&self, rid: EntityRid, rel: RelationId) -> Vec<EntityRid> {
+        let Some(view) = self.relation_defs.view(rel) else { return Vec::new() };
+        self.relations.related(view, rid)
+    }
+
+    fn related_from(&self, rid: EntityRid, rel: RelationId) -> Vec<EntityRid> {
+        let Some(view) = self.relation_defs.view(rel) else { return Vec::new() };
+        self.relations.related_from(view, rid)
+    }
+
+    fn relation_attrs(&self, from: EntityRid, rel: RelationId, to: EntityRid) -> Option<&EdgeAttrs> {
+        self.relations.edge(self.relation_defs.view(rel)?, from, to)
     }
 
     fn has_trait(&self, rid: EntityRid, t: TraitId) -> bool {
@@ -1,7 +1,271 @@
-use wmms_core::ids::{ RelationId};
+use std::collections::{BTreeMap, BTreeSet};
 
-use wmms_core::ids::EntityInstId;
+use wmms_core::{canon::CanonMap, hash::StableHash, ids::{AttrKeyId, EntityRid, RelationId}};
 
-pub struct Relation {
-    pub out_edges: Vec<Vec<(RelationId,EntityInstId)>>,
-}
\ No newline at end of file
+use crate::{attr::AttrValue, error::RelationError};
+
+/// How many edges of a relation an entity may have on each side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Cardinality {
+    OneToOne,
+    /// A source has any number of targets, a target at most one source
+    /// (e.g. `parent_of`).
+    OneToMany,
+    ManyToMany,
+}
+
+/// Declaration of a directed relation type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelationDef {
+    pub id: RelationId,
+    pub name: String,
+    pub cardinality: Cardinality,
+    /// `a rel b` implies `b rel a`, e.g. `ally_of`.
+    pub symmetric: bool,
+    /// Name of the reverse view, e.g. `child_of` for `parent_of`. Edges are
+    /// stored once, under this relation.
+    pub inverse: Option<String>,
+}
+
+impl RelationDef {
+    pub fn new(name: &str, cardinality: Cardinality) -> Self {
+        Self { id: RelationId::new(name), name: name.to_string(), cardinality, symmetric: false, inverse: None }
+    }
+
+    pub fn symmetric(mut self) -> Self {
+        self.symmetric = true;
+        self
+    }
+
+    pub fn inverse(mut self, name: &str) -> Self {
+        self.inverse = Some(name.to_string());
+        self
+    }
+}
+
+/// A relation as stored: the declared one, possibly viewed backwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelationView<'a> {
+    pub def: &'a RelationDef,
+    /// True when the id named the inverse, so `from` and `to` swap.
+    pub reversed: bool,
+}
+
+/// Declared relation types, by id and by inverse id.
+#[derive(Clone, Debug, Default)]
+pub struct RelationRegistry {
+    by_id: BTreeMap<RelationId, RelationDef>,
+    inverses: BTreeMap<RelationId, RelationId>,
+}
+
+impl RelationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a relation; its name and inverse name must both be unused, and a
+    /// symmetric relation can neither be one-to-many nor have an inverse.
+    pub fn register(&mut self, def: RelationDef) -> Result<(), RelationError> {
+        if self.view(def.id).is_some() {
+            return Err(RelationError::Duplicate(def.name));
+        }
+        if def.symmetric && (def.cardinality == Cardinality::OneToMany || def.inverse.is_some()) {
+            return Err(RelationError::Asymmetric(def.name));
+        }
+        if let Some(inverse) = &def.inverse {
+            let inv = RelationId::new(inverse);
+            if inv == def.id || self.view(inv).is_some() {
+                return Err(RelationError::Duplicate(inverse.clone()));
+            }
+            self.inverses.insert(inv, def.id);
+        }
+        self.by_id.insert(def.id, def);
+        Ok(())
+    }
+
+    pub fn get(&self, id: RelationId) -> Option<&RelationDef> {
+        self.by_id.get(&id)
+    }
+
+    /// Resolves a relation or inverse id.
+    pub fn view(&self, id: RelationId) -> Option<RelationView<'_>> {
+        if let Some(def) = self.by_id.get(&id) {
+            return Some(RelationView { def, reversed: false });
+        }
+        let def = self.by_id.get(self.inverses.get(&id)?)?;
+        Some(RelationView { def, reversed: true })
+    }
+
+    pub fn view_or_err(&self, id: RelationId) -> Result<RelationView<'_>, RelationError> {
+        self.view(id).ok_or_else(|| RelationError::Unknown(id.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RelationDef> + '_ {
+        self.by_id.values()
+    }
+}
+
+/// Per-edge attributes.
+pub type EdgeAttrs = CanonMap<AttrKeyId, AttrValue>;
+
+/// Edges of every relation, indexed in both directions.
+///
+/// Edges are kept under the declared relation with symmetric ones stored
+/// once, lower RID first; [`RelationView`] maps inverse and symmetric
+/// lookups onto that.
+#[derive(Clone, Debug, Default, StableHash)]
+pub struct RelationStore {
+    out: BTreeMap<EntityRid, BTreeMap<RelationId, BTreeMap<EntityRid, EdgeAttrs>>>,
+    #[stable_hash(skip)]
+    inc: BTreeMap<EntityRid, BTreeMap<RelationId, BTreeSet<EntityRid>>>,
+}
+
+impl RelationStore {
+    /// Stored `(source, target)` for an edge seen through `view`.
+    fn stored(view: RelationView<'_>, from: EntityRid, to: EntityRid) -> (EntityRid, EntityRid) {
+        if view.def.symmetric {
+            (from.min(to), from.max(to))
+        } else if view.reversed {
+            (to, from)
+        } else {
+            (from, to)
+        }
+    }
+
+    fn targets(&self, rid: EntityRid, rel: RelationId) -> impl Iterator<Item = EntityRid> + '_ {
+        self.out.get(&rid).and_then(|m| m.get(&rel)).into_iter().flat_map(|m| m.keys().copied())
+    }
+
+    fn sources(&self, rid: EntityRid, rel: RelationId) -> impl Iterator<Item = EntityRid> + '_ {
+        self.inc.get(&rid).and_then(|m| m.get(&rel)).into_iter().flat_map(|s| s.iter().copied())
+    }
+
+    /// Entities `x` with `rid view x`, in RID order.
+    pub fn related(&self, view: RelationView<'_>, rid: EntityRid) -> Vec<EntityRid> {
+        let id = view.def.id;
+        let mut out: Vec<EntityRid> = if view.def.symmetric {
+            self.targets(rid, id).chain(self.sources(rid, id)).collect()
+        } else if view.reversed {
+            self.sources(rid, id).collect()
+        } else {
+            self.targets(rid, id).collect()
+        };
+        out.sort();
+        out
+    }
+
+    /// Entities `x` with `x view rid`, in RID order.
+    pub fn related_from(&self, view: RelationView<'_>, rid: EntityRid) -> Vec<EntityRid> {
+        self.related(RelationView { reversed: !view.reversed, ..view }, rid)
+    }
+
+    pub fn edge(&self, view: RelationView<'_>, from: EntityRid, to: EntityRid) -> Option<&EdgeAttrs> {
+        let (src, dst) = Self::stored(view, from, to);
+        self.out.get(&src)?.get(&view.def.id)?.get(&dst)
+    }
+
+    /// A store holding only the edges that touch `rids`, enough to check
+    /// cardinality for edges between them.
+    pub(crate) fn subset(&self, rids: &BTreeSet<EntityRid>) -> RelationStore {
+        let mut out = RelationStore::default();
+        for &rid in rids {
+            for (&id, targets) in self.out.get(&rid).into_iter().flatten() {
+                for (&dst, attrs) in targets {
+                    out.link(rid, id, dst, attrs.clone());
+                }
+            }
+            for (&id, sources) in self.inc.get(&rid).into_iter().flatten() {
+                for &src in sources {
+                    out.link(src, id, rid, EdgeAttrs::new());
+                }
+            }
+        }
+        out
+    }
+
+    fn link(&mut self, src: EntityRid, id: RelationId, dst: EntityRid, attrs: EdgeAttrs) {
+        self.out.entry(src).or_default().entry(id).or_default().insert(dst, attrs);
+        self.inc.entry(dst).or_default().entry(id).or_default().insert(src);
+    }
+
+    /// Adds `from view to`, or replaces its attributes if it exists. Returns
+    /// the stored endpoints. An entity cannot be related to itself.
+    pub(crate) fn insert(
+        &mut self,
+        view: RelationView<'_>,
+        from: EntityRid,
+        to: EntityRid,
+        attrs: EdgeAttrs,
+    ) -> Result<(EntityRid, EntityRid), RelationError> {
+        if from == to {
+            return Err(RelationError::SelfEdge { relation: view.def.name.clone(), entity: from });
+        }
+        let (src, dst) = Self::stored(view, from, to);
+        let id = view.def.id;
+        if self.edge(view, from, to).is_none() {
+            let taken = match (view.def.cardinality, view.def.symmetric) {
+                (Cardinality::ManyToMany, _) => None,
+                (_, true) => [src, dst]
+                    .into_iter()
+                    .find(|&r| self.targets(r, id).chain(self.sources(r, id)).next().is_some())
+                    .map(|r| ("partner", r)),
+                (Cardinality::OneToOne, false) if self.targets(src, id).next().is_some() => Some(("target", src)),
+                (_, false) if self.sources(dst, id).next().is_some() => Some(("source", dst)),
+                _ => None,
+            };
+            if let Some((side, entity)) = taken {
+                return Err(RelationError::Cardinality { relation: view.def.name.clone(), side, entity });
+            }
+        }
+        self.link(src, id, dst, attrs);
+        Ok((src, dst))
+    }
+
+    /// Removes `from view to`; returns the stored endpoints if it existed.
+    pub(crate) fn remove(&mut self, view: RelationView<'_>, from: EntityRid, to: EntityRid) -> Option<(EntityRid, EntityRid)> {
+        let (src, dst) = Self::stored(view, from, to);
+        self.unlink(src, view.def.id, dst).then_some((src, dst))
+    }
+
+    fn unlink(&mut self, src: EntityRid, id: RelationId, dst: EntityRid) -> bool {
+        let removed = prune(&mut self.out, src, id, |targets| targets.remove(&dst).is_some());
+        prune(&mut self.inc, dst, id, |sources| sources.remove(&src));
+        removed
+    }
+
+    /// Removes every edge touching `rid`, returning them as stored.
+    pub(crate) fn remove_entity(&mut self, rid: EntityRid) -> Vec<(EntityRid, RelationId, EntityRid)> {
+        let mut gone = Vec::new();
+        for (id, targets) in self.out.get(&rid).into_iter().flatten() {
+            gone.extend(targets.keys().map(|&dst| (rid, *id, dst)));
+        }
+        for (id, sources) in self.inc.get(&rid).into_iter().flatten() {
+            gone.extend(sources.iter().map(|&src| (src, *id, rid)));
+        }
+        gone.sort();
+        gone.dedup();
+        for &(src, id, dst) in &gone {
+            self.unlink(src, id, dst);
+        }
+        gone
+    }
+}
+
+/// Runs `f` on the inner collection at `rid`/`id`, dropping emptied levels.
+fn prune<T: Default + PartialEq>(
+    map: &mut BTreeMap<EntityRid, BTreeMap<RelationId, T>>,
+    rid: EntityRid,
+    id: RelationId,
+    f: impl FnOnce(&mut T) -> bool,
+) -> bool {
+    let Some(by_rel) = map.get_mut(&rid) else { return false };
+    let Some(inner) = by_rel.get_mut(&id) else { return false };
+    let hit = f(inner);
+    if *inner == T::default() {
+        by_rel.remove(&id);
+        if by_rel.is_empty() {
+            map.remove(&rid);
+        }
+    }
+    hit
+}
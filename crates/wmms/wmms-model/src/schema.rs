@@ -0,0 +1,430 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use wmms_core::{ids::AttrKeyId, num::{Decimal, Q16_16, Q32_32}};
+
+use crate::attr::{AttrValue, ModifierOp, cmp_numeric, from_q32, to_q32};
+use crate::error::AttrError;
+use crate::formula::Formula;
+
+/// Declared type of an attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AttrType {
+    Bool,
+    Int,
+    Fixed,
+    Wide,
+    Decimal,
+    Float,
+    Str,
+    Entity,
+    Trait,
+    Ability,
+    Effect,
+    Archetype,
+    Aspect,
+    Tick,
+    TickDelta,
+    Vec2,
+    Vec3,
+    Range,
+    List,
+    Set,
+    Map,
+}
+
+impl AttrType {
+    pub fn of(value: &AttrValue) -> Option<AttrType> {
+        Some(match value {
+            AttrValue::Null => return None,
+            AttrValue::Bool(_) => AttrType::Bool,
+            AttrValue::Int(_) => AttrType::Int,
+            AttrValue::Fixed(_) => AttrType::Fixed,
+            AttrValue::Wide(_) => AttrType::Wide,
+            AttrValue::Decimal(_) => AttrType::Decimal,
+            AttrValue::Float(_) => AttrType::Float,
+            AttrValue::Str(_) => AttrType::Str,
+            AttrValue::Entity(_) => AttrType::Entity,
+            AttrValue::Trait(_) => AttrType::Trait,
+            AttrValue::Ability(_) => AttrType::Ability,
+            AttrValue::Effect(_) => AttrType::Effect,
+            AttrValue::Archtetype(_) => AttrType::Archetype,
+            AttrValue::Aspect(_) => AttrType::Aspect,
+            AttrValue::Tick(_) => AttrType::Tick,
+            AttrValue::TickDelta(_) => AttrType::TickDelta,
+            AttrValue::Vec2(_) => AttrType::Vec2,
+            AttrValue::Vec3(_) => AttrType::Vec3,
+            AttrValue::Range { .. } => AttrType::Range,
+            AttrValue::List(_) => AttrType::List,
+            AttrValue::Set(_) => AttrType::Set,
+            AttrValue::Map(_) => AttrType::Map,
+        })
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, AttrType::Int | AttrType::Fixed | AttrType::Wide | AttrType::Decimal | AttrType::Float)
+    }
+
+    pub fn is_collection(self) -> bool {
+        matches!(self, AttrType::List | AttrType::Set | AttrType::Map)
+    }
+}
+
+impl fmt::Display for AttrType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AttrType::Bool => "bool",
+            AttrType::Int => "int",
+            AttrType::Fixed => "fixed",
+            AttrType::Wide => "wide",
+            AttrType::Decimal => "decimal",
+            AttrType::Float => "float",
+            AttrType::Str => "str",
+            AttrType::Entity => "entity",
+            AttrType::Trait => "trait",
+            AttrType::Ability => "ability",
+            AttrType::Effect => "effect",
+            AttrType::Archetype => "archetype",
+            AttrType::Aspect => "aspect",
+            AttrType::Tick => "tick",
+            AttrType::TickDelta => "tick_delta",
+            AttrType::Vec2 => "vec2",
+            AttrType::Vec3 => "vec3",
+            AttrType::Range => "range",
+            AttrType::List => "list",
+            AttrType::Set => "set",
+            AttrType::Map => "map",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Editor-facing description of an attribute.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AttrEditorMeta {
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// Unit shown next to the value, e.g. `hp` or `%`.
+    pub unit: Option<String>,
+    pub category: Option<String>,
+}
+
+/// Declared type, default and constraints of one attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrSchema {
+    pub key: AttrKeyId,
+    /// Canonical name the key was minted from, e.g. `health.max`.
+    pub name: String,
+    pub ty: AttrType,
+    /// Type of list and set elements or map values; `None` leaves a
+    /// collection untyped.
+    pub element: Option<AttrType>,
+    pub default: Option<AttrValue>,
+    pub min: Option<AttrValue>,
+    pub max: Option<AttrValue>,
+    /// Allowed values; empty means any value of the type.
+    pub domain: Vec<AttrValue>,
+    pub editor: AttrEditorMeta,
+    /// Set for derived attributes, recomputed by the model when inputs change.
+    pub formula: Option<Formula>,
+}
+
+impl AttrSchema {
+    pub fn new(name: &str, ty: AttrType) -> Self {
+        Self {
+            key: AttrKeyId::new(name),
+            name: name.to_string(),
+            ty,
+            element: None,
+            default: None,
+            min: None,
+            max: None,
+            domain: Vec::new(),
+            editor: AttrEditorMeta::default(),
+            formula: None,
+        }
+    }
+
+    /// Declares the element type of a collection attribute.
+    pub fn elements(mut self, ty: AttrType) -> Self {
+        self.element = Some(ty);
+        self
+    }
+
+    pub fn default_value(mut self, value: AttrValue) -> Self {
+        self.default = Some(value);
+        self
+    }
+
+    /// Inclusive bounds for numeric attributes.
+    pub fn range(mut self, min: Option<AttrValue>, max: Option<AttrValue>) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn domain(mut self, values: impl IntoIterator<Item = AttrValue>) -> Self {
+        self.domain = values.into_iter().collect();
+        self
+    }
+
+    pub fn editor(mut self, editor: AttrEditorMeta) -> Self {
+        self.editor = editor;
+        self
+    }
+
+    /// Makes the attribute derived: its base value is `formula` over other
+    /// attributes, and other layers still apply on top.
+    pub fn derived(mut self, formula: Formula) -> Self {
+        self.formula = Some(formula);
+        self
+    }
+
+    /// A formula result as a value of the attribute's type.
+    pub fn derived_value(&self, q: Q32_32) -> AttrValue {
+        let like = match self.ty {
+            AttrType::Int => AttrValue::Int(0),
+            AttrType::Fixed => AttrValue::Fixed(Q16_16::ZERO),
+            AttrType::Float => AttrValue::Float(0.0),
+            AttrType::Decimal => match self.default {
+                Some(AttrValue::Decimal(d)) => AttrValue::Decimal(d),
+                _ => AttrValue::Decimal(Decimal::ZERO),
+            },
+            _ => AttrValue::Wide(q),
+        };
+        from_q32(q, &like)
+    }
+
+    /// Brings a numeric value inside the declared range. Layers may push a
+    /// resolved value past it, e.g. `Base 90` plus `Add 1000` on a [0, 100]
+    /// attribute, so the model clamps every resolved value with this.
+    pub fn clamp(&self, value: AttrValue) -> AttrValue {
+        if let Some(min) = &self.min
+            && cmp_numeric(&value, min) == Some(Ordering::Less)
+        {
+            return bound_as(min, &value);
+        }
+        if let Some(max) = &self.max
+            && cmp_numeric(&value, max) == Some(Ordering::Greater)
+        {
+            return bound_as(max, &value);
+        }
+        value
+    }
+
+    /// Checks a value the attribute may take.
+    pub fn check_value(&self, value: &AttrValue) -> Result<(), AttrError> {
+        // Null clears an attribute and is always allowed.
+        if matches!(value, AttrValue::Null) {
+            return Ok(());
+        }
+        self.check_type(value, AttrType::of(value) == Some(self.ty))?;
+        self.check_elements(value)?;
+        if !self.domain.is_empty() && !self.domain.contains(value) {
+            let domain = self.domain.iter().map(|v| format!("{v:?}")).collect::<Vec<_>>().join(", ");
+            return Err(AttrError::NotInDomain {
+                attr: self.name.clone(),
+                value: format!("{value:?}"),
+                domain: format!("[{domain}]"),
+            });
+        }
+        let below = self.min.as_ref().is_some_and(|min| cmp_numeric(value, min) == Some(Ordering::Less));
+        let above = self.max.as_ref().is_some_and(|max| cmp_numeric(value, max) == Some(Ordering::Greater));
+        if below || above {
+            let bound = |b: &Option<AttrValue>| b.as_ref().map(|v| format!("{v:?}")).unwrap_or_else(|| "..".into());
+            return Err(AttrError::OutOfRange {
+                attr: self.name.clone(),
+                value: format!("{value:?}"),
+                range: format!("[{}, {}]", bound(&self.min), bound(&self.max)),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks a layer value for `op`.
+    ///
+    /// `Base` and `Final` layers must be valid values. Arithmetic layers on a
+    /// numeric attribute accept any numeric operand, so an `Int` attribute can
+    /// take a `Fixed` ×1.5 factor; clamp bounds only need the right type.
+    /// `Append` and `Remove` only apply to collections, and a map only
+    /// appends another map; added elements must have the declared element
+    /// type. Removing from a map takes keys, which are always strings.
+    pub fn check_layer(&self, op: ModifierOp, value: &AttrValue) -> Result<(), AttrError> {
+        match op {
+            ModifierOp::Base | ModifierOp::Final => self.check_value(value),
+            ModifierOp::Append if self.ty == AttrType::Map => {
+                self.check_type(value, matches!(value, AttrValue::Map(_)))?;
+                self.check_elements(value)
+            }
+            ModifierOp::Remove if self.ty == AttrType::Map => Ok(()),
+            ModifierOp::Append | ModifierOp::Remove => {
+                self.check_type(value, self.ty.is_collection())?;
+                self.check_elements(value)
+            }
+            _ => {
+                let numeric = AttrType::of(value).is_some_and(AttrType::is_numeric);
+                self.check_type(value, self.ty.is_numeric() && numeric)
+            }
+        }
+    }
+
+    /// Checks list or set elements, or map values, against `element`.
+    fn check_elements(&self, value: &AttrValue) -> Result<(), AttrError> {
+        let Some(expected) = self.element else { return Ok(()) };
+        let bad = match value {
+            AttrValue::Map(map) => map.values().find(|v| AttrType::of(v) != Some(expected)),
+            v => v.elements().iter().find(|v| AttrType::of(v) != Some(expected)),
+        };
+        match bad {
+            Some(v) => Err(AttrError::ElementMismatch {
+                attr: self.name.clone(),
+                expected,
+                found: AttrType::of(v).map_or_else(|| "null".to_string(), |t| t.to_string()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn check_type(&self, value: &AttrValue, ok: bool) -> Result<(), AttrError> {
+        if ok {
+            return Ok(());
+        }
+        Err(AttrError::TypeMismatch {
+            attr: self.name.clone(),
+            expected: self.ty,
+            found: AttrType::of(value).map_or_else(|| "null".to_string(), |t| t.to_string()),
+        })
+    }
+}
+
+/// A range bound as the type of the value it clamps.
+fn bound_as(bound: &AttrValue, like: &AttrValue) -> AttrValue {
+    match (bound, like) {
+        (AttrValue::Int(_), AttrValue::Int(_)) => bound.clone(),
+        _ => to_q32(bound).map_or_else(|| like.clone(), |q| from_q32(q, like)),
+    }
+}
+
+/// All declared attribute schemas, by key.
+#[derive(Clone, Debug, Default)]
+pub struct AttrSchemaRegistry {
+    by_key: BTreeMap<AttrKeyId, AttrSchema>,
+    /// Derived attributes, each after the derived attributes it reads.
+    derived_order: Vec<AttrKeyId>,
+}
+
+impl AttrSchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a schema; its default, if any, must satisfy it, and a formula
+    /// must not depend on the attribute itself, directly or not.
+    pub fn register(&mut self, schema: AttrSchema) -> Result<(), AttrError> {
+        if self.by_key.contains_key(&schema.key) {
+            return Err(AttrError::Duplicate(schema.name));
+        }
+        if let Some(default) = &schema.default {
+            schema.check_value(default)?;
+        }
+        if let Some(formula) = &schema.formula {
+            if !schema.ty.is_numeric() {
+                return Err(AttrError::DerivedNotNumeric(schema.name));
+            }
+            let mut path = vec![schema.key];
+            if self.reaches(formula, schema.key, &mut path) {
+                path.push(schema.key);
+                let names: Vec<String> = path.iter().map(|k| self.name_of(*k, &schema)).collect();
+                return Err(AttrError::Cycle(names.join(" -> ")));
+            }
+        }
+        self.by_key.insert(schema.key, schema);
+        self.derived_order = self.topo_order();
+        Ok(())
+    }
+
+    /// True if `formula` reads `target` through any chain of derived
+    /// attributes, leaving that chain in `path`.
+    fn reaches(&self, formula: &Formula, target: AttrKeyId, path: &mut Vec<AttrKeyId>) -> bool {
+        for input in formula.inputs() {
+            if input.key == target {
+                return true;
+            }
+            if path.contains(&input.key) {
+                continue;
+            }
+            if let Some(next) = self.get(input.key).and_then(|s| s.formula.as_ref()) {
+                path.push(input.key);
+                if self.reaches(next, target, path) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+
+    fn name_of(&self, key: AttrKeyId, pending: &AttrSchema) -> String {
+        match self.get(key) {
+            Some(s) => s.name.clone(),
+            None if key == pending.key => pending.name.clone(),
+            None => key.to_string(),
+        }
+    }
+
+    fn topo_order(&self) -> Vec<AttrKeyId> {
+        fn visit(reg: &AttrSchemaRegistry, key: AttrKeyId, out: &mut Vec<AttrKeyId>) {
+            let Some(formula) = reg.get(key).and_then(|s| s.formula.as_ref()) else { return };
+            if out.contains(&key) {
+                return;
+            }
+            for input in formula.inputs() {
+                visit(reg, input.key, out);
+            }
+            out.push(key);
+        }
+        let mut out = Vec::new();
+        for key in self.by_key.keys() {
+            visit(self, *key, &mut out);
+        }
+        out
+    }
+
+    /// Derived attribute schemas, each after the derived attributes it reads.
+    pub fn derived(&self) -> impl Iterator<Item = (&AttrSchema, &Formula)> + '_ {
+        self.derived_order.iter().filter_map(|k| {
+            let schema = self.get(*k)?;
+            Some((schema, schema.formula.as_ref()?))
+        })
+    }
+
+    pub fn get(&self, key: AttrKeyId) -> Option<&AttrSchema> {
+        self.by_key.get(&key)
+    }
+
+    /// Schemas in key order.
+    pub fn iter(&self) -> impl Iterator<Item = &AttrSchema> + '_ {
+        self.by_key.values()
+    }
+
+    /// `value` clamped to the range of `key`'s schema, if it has one.
+    pub fn clamp(&self, key: AttrKeyId, value: AttrValue) -> AttrValue {
+        match self.get(key) {
+            Some(schema) => schema.clamp(value),
+            None => value,
+        }
+    }
+
+    pub fn default_value(&self, key: AttrKeyId) -> Option<&AttrValue> {
+        self.get(key)?.default.as_ref()
+    }
+
+    pub fn schema(&self, key: AttrKeyId) -> Result<&AttrSchema, AttrError> {
+        self.get(key).ok_or_else(|| AttrError::Unknown(key.to_string()))
+    }
+
+    pub fn check_layer(&self, key: AttrKeyId, op: ModifierOp, value: &AttrValue) -> Result<(), AttrError> {
+        self.schema(key)?.check_layer(op, value)
+    }
+}
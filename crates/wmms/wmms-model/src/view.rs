@@ -1,7 +1,7 @@
-use wmms_aspects::{query::AspectQuery, set::AspectSet};
-use wmms_core::ids::{AttrKeyId, TraitId,EntityId, EntityRid};
+use wmms_aspects::{query::{AspectQuery, QueryExplain}, set::AspectSet};
+use wmms_core::{ids::{AttrKeyId, TraitId,EntityId, EntityRid, RelationId}, num::{Decimal, Q16_16, Q32_32}};
 
-use crate::{attr::{AttrLayer, AttrValue}};
+use crate::{attr::{AttrExplain, AttrValue}, relations::EdgeAttrs};
 
 pub trait ModelView {
     fn has_entity(&self, id: EntityId) -> bool;
@@ -10,9 +10,41 @@ pub trait ModelView {
 
     fn aspects(&self, rid: EntityRid) -> &AspectSet;
     fn matches(&self, rid: EntityRid, q: &AspectQuery) -> bool;
+    fn explain_match(&self, rid: EntityRid, q: &AspectQuery) -> Option<QueryExplain>;
 
     fn get_attr(&self, rid: EntityRid, key: AttrKeyId) -> Option<&AttrValue>;
-    fn explain_attr(&self, rid: EntityRid, key: AttrKeyId) -> Option<&[AttrLayer]>;
+    fn explain_attr(&self, rid: EntityRid, key: AttrKeyId) -> Option<AttrExplain>;
 
     fn has_trait(&self, rid: EntityRid, t: TraitId) -> bool;
+
+    /// Entities `x` with `rid rel x`; `rel` may name an inverse relation.
+    fn related(&self, rid: EntityRid, rel: RelationId) -> Vec<EntityRid>;
+    /// Entities `x` with `x rel rid`.
+    fn related_from(&self, rid: EntityRid, rel: RelationId) -> Vec<EntityRid>;
+    fn relation_attrs(&self, from: EntityRid, rel: RelationId, to: EntityRid) -> Option<&EdgeAttrs>;
+
+    fn get_bool(&self, rid: EntityRid, key: AttrKeyId) -> Option<bool> {
+        self.get_attr(rid, key)?.as_bool()
+    }
+    fn get_int(&self, rid: EntityRid, key: AttrKeyId) -> Option<i64> {
+        self.get_attr(rid, key)?.as_int()
+    }
+    fn get_fixed(&self, rid: EntityRid, key: AttrKeyId) -> Option<Q16_16> {
+        self.get_attr(rid, key)?.as_fixed()
+    }
+    fn get_wide(&self, rid: EntityRid, key: AttrKeyId) -> Option<Q32_32> {
+        self.get_attr(rid, key)?.as_wide()
+    }
+    fn get_decimal(&self, rid: EntityRid, key: AttrKeyId) -> Option<Decimal> {
+        self.get_attr(rid, key)?.as_decimal()
+    }
+    fn get_float(&self, rid: EntityRid, key: AttrKeyId) -> Option<f32> {
+        self.get_attr(rid, key)?.as_float()
+    }
+    fn get_str(&self, rid: EntityRid, key: AttrKeyId) -> Option<&str> {
+        self.get_attr(rid, key)?.as_str()
+    }
+    fn get_entity(&self, rid: EntityRid, key: AttrKeyId) -> Option<EntityId> {
+        self.get_attr(rid, key)?.as_entity()
+    }
 }
\ No newline at end of file
@@ -1,3 +1,93 @@
-fn main() {
-    println!("Hello, world!");
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use miette::{IntoDiagnostic, WrapErr, miette};
+use wmms_aspects::registry::{AspectRegistry, AspectRegistryBuilder};
+use world_universe::{universe::UniverseManifest, world_root::WorldRoot};
+
+const USAGE: &str = "usage: world lint [ASPECT_FILE...]\n       world tree [--format dot|mermaid|json] [ASPECT_FILE...]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("lint") => lint(&args[1..]),
+        Some("tree") => tree(&args[1..]),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(report) => {
+            eprintln!("{report:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Checks the declared aspect paths against the manifest's taxonomy policy.
+fn lint(files: &[String]) -> miette::Result<()> {
+    let registry = load_registry(files, true)?;
+    println!("{} aspects follow the taxonomy policy", registry.len());
+    Ok(())
+}
+
+/// Prints the aspect tree, as an indented outline by default.
+fn tree(args: &[String]) -> miette::Result<()> {
+    let (format, files) = match args {
+        [flag, format, files @ ..] if flag == "--format" => (Some(format.as_str()), files),
+        files => (None, files),
+    };
+    let registry = load_registry(files, false)?;
+    let export = registry.export();
+    match format {
+        None => {
+            let mut below = Vec::new();
+            for root in registry.roots() {
+                println!("{}", registry.key(root).as_str());
+                registry.descendants(root, None, &mut below);
+                for &rid in &below {
+                    let node = registry.node(rid);
+                    let name = node.key.as_str().rsplit('.').next().unwrap_or_default();
+                    println!("{}{name}", "  ".repeat(node.depth as usize));
+                }
+            }
+        }
+        Some("dot") => print!("{}", export.to_dot()),
+        Some("mermaid") => print!("{}", export.to_mermaid()),
+        Some("json") => print!("{}", export.to_json()),
+        Some(other) => return Err(miette!("unknown format '{other}', expected dot, mermaid or json")),
+    }
+    Ok(())
+}
+
+/// Builds a registry from the given aspect list files, or the manifest's
+/// `aspects.sources` if none are given.
+fn load_registry(files: &[String], enforce_policy: bool) -> miette::Result<AspectRegistry> {
+    let root = WorldRoot::discover().map_err(|e| miette!("{e}"))?;
+    let manifest = UniverseManifest::load(&root)
+        .map_err(|e| miette!("cannot load {}: {e}", root.get_universe_manifest_path().display()))?;
+    let sources: Vec<PathBuf> = if files.is_empty() {
+        manifest.aspects.sources.iter().map(|p| root.path.join(p)).collect()
+    } else {
+        files.iter().map(PathBuf::from).collect()
+    };
+
+    let mut builder = AspectRegistryBuilder::new();
+    if enforce_policy {
+        builder = builder.with_policy(manifest.aspects.policy());
+    }
+    for source in &sources {
+        let text = std::fs::read_to_string(source)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("cannot read {}", source.display()))?;
+        for line in text.lines() {
+            let path = line.split('#').next().unwrap_or_default().trim();
+            if !path.is_empty() {
+                builder.register(path)?;
+            }
+        }
+    }
+    Ok(builder.seal()?)
 }
@@ -1,6 +1,9 @@
 
 
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
+use wmms_aspects::policy::TaxonomyPolicy;
 
 use crate::world_root::WorldRoot;
 
@@ -10,7 +13,33 @@ pub struct UniverseManifest {
     pub version: Option<String>,
     pub authors: Option<Vec<String>>,
     pub default_scope: Option<String>,
+    #[serde(default)]
+    pub aspects: AspectsManifest,
+}
+
+/// `[aspects]` table: taxonomy policy and the files declaring aspect paths.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AspectsManifest {
+    /// Allowed root namespaces; the recommended set if omitted, any namespace if empty.
+    pub roots: Option<Vec<String>>,
+    pub negative_prefixes: Option<Vec<String>>,
+    /// Aspect list files, relative to the world root: one path per line, `#` comments.
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+}
 
+impl AspectsManifest {
+    pub fn policy(&self) -> TaxonomyPolicy {
+        let defaults = TaxonomyPolicy::default();
+        TaxonomyPolicy {
+            roots: match &self.roots {
+                None => defaults.roots,
+                Some(roots) if roots.is_empty() => None,
+                Some(roots) => Some(roots.iter().cloned().collect()),
+            },
+            negative_prefixes: self.negative_prefixes.clone().unwrap_or(defaults.negative_prefixes),
+        }
+    }
 }
 
 impl UniverseManifest {
@@ -20,11 +49,12 @@ impl UniverseManifest {
             version: None,
             authors,
             default_scope: None,
+            aspects: AspectsManifest::default(),
         }
     }
-    pub fn load(_path: &WorldRoot) -> Result<Self, Box<dyn std::error::Error>> {
-        // Placeholder for loading logic
-        Ok(Self::new("DefaultUniverse", None))
+    pub fn load(root: &WorldRoot) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(root.get_universe_manifest_path())?;
+        Ok(toml::from_str(&text)?)
     }
     pub fn save(&self, _root: WorldRoot) -> Result<(), Box<dyn std::error::Error>> {
         // Placeholder for saving logic